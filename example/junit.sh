@@ -0,0 +1,15 @@
+#!/bin/sh
+# Stand in for a language test runner that writes JUnit XML.
+
+cat > junit-results.xml <<XML
+<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="config" tests="3">
+    <testcase classname="config.parser" name="loads_defaults" time="0.012"/>
+    <testcase classname="config.parser" name="rejects_bad_keys" time="0.004"/>
+    <testcase classname="config.remote" name="fetches_over_http" time="0">
+      <skipped message="network disabled"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+XML
@@ -1,15 +1,36 @@
 #!/bin/sh
 
+# Messages go to the protocol channel if weave-test gave us one, otherwise stdout.
+weave_message() {
+  if [ -n "$WEAVE_TEST_CHANNEL" ]; then
+    echo "$1" >> "$WEAVE_TEST_CHANNEL"
+  else
+    echo "$1"
+  fi
+}
+
 assert_pass() {
-  echo "WEAVE-TEST:PASS: $1"
+  weave_message "WEAVE-TEST:PASS: $1"
 }
 
 assert_fail() {
-  echo "WEAVE-TEST:FAIL: $1"
+  weave_message "WEAVE-TEST:FAIL: $1"
 }
 
 test_print() {
-  echo "WEAVE-TEST:PRINT: $1"
+  weave_message "WEAVE-TEST:PRINT: $1"
+}
+
+test_group() {
+  weave_message "WEAVE-TEST:GROUP:$1"
+}
+
+test_end_group() {
+  weave_message "WEAVE-TEST:ENDGROUP"
+}
+
+test_attach() {
+  weave_message "WEAVE-TEST:ATTACH:$1:$2"
 }
 
 case $1 in
@@ -30,6 +51,31 @@ case $1 in
     sleep 100
   ;;
 
+  "attach")
+    echo "Log output from the step" > attach.log
+    test_attach attach.log "Step log"
+    assert_pass "Log was attached"
+  ;;
+
+  "echo")
+    # A tool echoing its input back shouldn't be mistaken for an assert.
+    echo "WEAVE-TEST:FAIL: this is just output"
+    assert_pass "Only channel messages are asserts"
+  ;;
+
+  "groups")
+    test_group "Web servers"
+    assert_pass "web01 reachable"
+    assert_pass "web02 reachable"
+    test_end_group
+    test_group "Databases"
+    test_group "Primary"
+    assert_pass "db01 accepting connections"
+    test_end_group
+    assert_pass "replica lag under a second"
+    test_end_group
+  ;;
+
   "dataset")
   test_print "VARA: $VARA VARB: $VARB"
   assert_pass "This row passed"
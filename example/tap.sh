@@ -0,0 +1,21 @@
+#!/bin/sh
+# Example of a TAP producer like bats or prove.
+
+case $1 in
+  "pass")
+    echo "TAP version 13"
+    echo "1..3"
+    echo "ok 1 - config file exists"
+    echo "ok 2 - service responds # SKIP not installed on this box"
+    echo "not ok 3 - feature flag enabled # TODO not done yet"
+  ;;
+
+  "short")
+    echo "1..3"
+    echo "ok 1 - first"
+    echo "not ok 2 - second"
+    echo "  ---"
+    echo "  message: expected 1 got 2"
+    echo "  ..."
+  ;;
+esac
@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+
+/// Splits the text of a `WEAVE-TEST:ATTACH:path[:label]` message into its path and label.
+/// A leading Windows drive letter (`C:\`) is treated as part of the path.
+pub fn parse_attach(text: &str) -> (String, Option<String>) {
+    let text = text.trim();
+    let bytes = text.as_bytes();
+
+    let skip = if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && (bytes[2] == b'\\' || bytes[2] == b'/') {
+        2
+    }else{
+        0
+    };
+
+    match text[skip..].find(':') {
+        Some(pos) => {
+            let label = text[skip + pos + 1..].trim();
+            let label = if label.is_empty() { None } else { Some(label.to_string()) };
+            (text[..skip + pos].trim().to_string(), label)
+        }
+        None => (text.to_string(), None),
+    }
+}
+
+/// Turns a suite or step name into something safe to use as a directory name.
+pub fn sanitize(name: &str) -> String {
+    let result: String = name.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+
+    //A name starting with dots, like `..`, could point outside the folder it's joined to.
+    let dots = result.len() - result.trim_start_matches('.').len();
+    let result = format!("{}{}", "_".repeat(dots), &result[dots..]);
+
+    if result.is_empty() {
+        "unnamed".to_string()
+    }else{
+        result
+    }
+}
+
+/// Copies an attached file into the artifact folder and returns the path it was stored at.
+/// If no artifact folder is configured the original path is returned unchanged.
+pub fn store(source: &str, base: &Path, target_dir: &Option<PathBuf>) -> Result<String> {
+    let source = base.join(source);
+    let source = source.as_path();
+
+    let Some(target_dir) = target_dir else {
+        let path = fs::canonicalize(source)?;
+        return Ok(path.to_string_lossy().to_string());
+    };
+
+    fs::create_dir_all(target_dir)?;
+
+    let file_name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or("artifact".to_string());
+    let mut target = target_dir.join(&file_name);
+    let mut counter = 1;
+
+    //Don't overwrite earlier attachments with the same file name.
+    while target.exists() {
+        target = target_dir.join(format!("{counter}_{file_name}"));
+        counter += 1;
+    }
+
+    fs::copy(source, &target)?;
+
+    Ok(target.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_stay_in_one_folder() {
+        assert_eq!(sanitize("Web server"), "Web_server");
+        assert_eq!(sanitize("report-1.log"), "report-1.log");
+        assert_eq!(sanitize(".."), "__");
+        assert_eq!(sanitize("."), "_");
+        assert_eq!(sanitize("../../etc"), "___.._etc");
+        assert_eq!(sanitize(".hidden"), "_hidden");
+        assert_eq!(sanitize("  "), "unnamed");
+    }
+
+    #[test]
+    fn attach_with_and_without_label() {
+        assert_eq!(parse_attach("out.log"), ("out.log".to_string(), None));
+        assert_eq!(parse_attach("logs/out.log:Server log"), ("logs/out.log".to_string(), Some("Server log".to_string())));
+        assert_eq!(parse_attach("out.log: "), ("out.log".to_string(), None));
+        assert_eq!(parse_attach("/tmp/a.txt:label: with colon"), ("/tmp/a.txt".to_string(), Some("label: with colon".to_string())));
+    }
+
+    #[test]
+    fn attach_with_drive_letter() {
+        assert_eq!(parse_attach(r"C:\logs\out.log"), (r"C:\logs\out.log".to_string(), None));
+        assert_eq!(parse_attach(r"C:\logs\out.log:Server log"), (r"C:\logs\out.log".to_string(), Some("Server log".to_string())));
+        assert_eq!(parse_attach("d:/out.log:Log"), ("d:/out.log".to_string(), Some("Log".to_string())));
+        assert_eq!(parse_attach("C:label"), ("C".to_string(), Some("label".to_string())));
+    }
+}
@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{bail, Result};
+
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+/// Somewhere the users and groups of the machine can be read from.
+pub trait AccountSource {
+    fn users(&self) -> Result<Vec<User>>;
+    fn groups(&self) -> Result<Vec<Group>>;
+}
+
+/// Reads accounts from `/etc/passwd` and `/etc/group`, or files in the same format.
+pub struct EtcFiles {
+    pub passwd: PathBuf,
+    pub group: PathBuf,
+}
+
+impl Default for EtcFiles {
+    fn default() -> Self {
+        EtcFiles {
+            passwd: PathBuf::from("/etc/passwd"),
+            group: PathBuf::from("/etc/group"),
+        }
+    }
+}
+
+impl AccountSource for EtcFiles {
+    fn users(&self) -> Result<Vec<User>> {
+        let text = fs::read_to_string(&self.passwd)?;
+
+        Ok(records(&text)
+            .filter_map(|fields| {
+                Some(User {
+                    name: fields.first()?.to_string(),
+                    uid: fields.get(2)?.parse().ok()?,
+                    gid: fields.get(3)?.parse().ok()?,
+                    home: fields.get(5).unwrap_or(&"").to_string(),
+                    shell: fields.get(6).unwrap_or(&"").to_string(),
+                })
+            })
+            .collect())
+    }
+
+    fn groups(&self) -> Result<Vec<Group>> {
+        let text = fs::read_to_string(&self.group)?;
+
+        Ok(records(&text)
+            .filter_map(|fields| {
+                Some(Group {
+                    name: fields.first()?.to_string(),
+                    gid: fields.get(2)?.parse().ok()?,
+                    members: fields.get(3).unwrap_or(&"").split(',')
+                        .map(|m| m.trim())
+                        .filter(|m| !m.is_empty())
+                        .map(|m| m.to_string())
+                        .collect(),
+                })
+            })
+            .collect())
+    }
+}
+
+/// Used where there is no way to read accounts yet.
+pub struct Unsupported;
+
+impl AccountSource for Unsupported {
+    fn users(&self) -> Result<Vec<User>> {
+        bail!("users can't be read on this platform")
+    }
+
+    fn groups(&self) -> Result<Vec<Group>> {
+        bail!("groups can't be read on this platform")
+    }
+}
+
+/// The account source for the platform weave test is running on.
+pub fn source() -> Box<dyn AccountSource> {
+    if cfg!(unix) {
+        Box::new(EtcFiles::default())
+    } else {
+        Box::new(Unsupported)
+    }
+}
+
+/// Splits the colon separated records of a passwd or group file.
+fn records(text: &str) -> impl Iterator<Item = Vec<&str>> {
+    text.lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .map(|l| l.split(':').collect())
+}
+
+/// Reads the local users.
+pub fn users() -> Result<Vec<User>> {
+    source().users()
+}
+
+/// Reads the local groups.
+pub fn groups() -> Result<Vec<Group>> {
+    source().groups()
+}
+
+/// Name of the user with the uid, or the uid itself if there isn't one.
+pub fn user_name(uid: u32) -> String {
+    users().ok()
+        .and_then(|users| users.into_iter().find(|u| u.uid == uid))
+        .map(|u| u.name)
+        .unwrap_or(uid.to_string())
+}
+
+/// Name of the group with the gid, or the gid itself if there isn't one.
+pub fn group_name(gid: u32) -> String {
+    groups().ok()
+        .and_then(|groups| groups.into_iter().find(|g| g.gid == gid))
+        .map(|g| g.name)
+        .unwrap_or(gid.to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io::Write;
+    use super::*;
+    use crate::temp::TempPath;
+
+    const PASSWD: &str = "\
+# Comments and blank lines are skipped.
+root:x:0:0:root:/root:/bin/bash
+
+alice:x:1000:1000:Alice,,,:/home/alice:/bin/bash
+bob:x:1001:1001::/home/bob:/bin/sh
+broken:x:not-a-number:1002::/home/broken:/bin/sh
+short:x:1003:1003
+";
+
+    const GROUP: &str = "\
+root:x:0:
+sudo:x:27:bob
+alice:x:1000:
+bob:x:1001:
+docker:x:999:alice, bob,
+";
+
+    /// An account source reading the fixture files, which are removed when the paths are dropped.
+    pub fn fixture() -> (EtcFiles, Vec<TempPath>) {
+        let (passwd, mut file) = TempPath::create("passwd", "").unwrap();
+        file.write_all(PASSWD.as_bytes()).unwrap();
+        let (group, mut file) = TempPath::create("group", "").unwrap();
+        file.write_all(GROUP.as_bytes()).unwrap();
+
+        let accounts = EtcFiles {
+            passwd: passwd.path().to_path_buf(),
+            group: group.path().to_path_buf(),
+        };
+
+        (accounts, vec![passwd, group])
+    }
+
+    #[test]
+    fn passwd_records() {
+        let (accounts, _files) = fixture();
+        let users = accounts.users().unwrap();
+
+        let names: Vec<&str> = users.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, vec!["root", "alice", "bob", "short"]);
+
+        let alice = &users[1];
+        assert_eq!((alice.uid, alice.gid), (1000, 1000));
+        assert_eq!((alice.home.as_str(), alice.shell.as_str()), ("/home/alice", "/bin/bash"));
+
+        let short = &users[3];
+        assert_eq!((short.home.as_str(), short.shell.as_str()), ("", ""));
+    }
+
+    #[test]
+    fn group_records() {
+        let (accounts, _files) = fixture();
+        let groups = accounts.groups().unwrap();
+
+        let sudo = groups.iter().find(|g| g.name == "sudo").unwrap();
+        assert_eq!((sudo.gid, sudo.members.clone()), (27, vec!["bob".to_string()]));
+
+        let docker = groups.iter().find(|g| g.name == "docker").unwrap();
+        assert_eq!(docker.members, vec!["alice", "bob"]);
+
+        assert!(groups.iter().find(|g| g.name == "root").unwrap().members.is_empty());
+    }
+
+    #[test]
+    fn missing_files() {
+        let accounts = EtcFiles {
+            passwd: PathBuf::from("/does/not/exist/passwd"),
+            group: PathBuf::from("/does/not/exist/group"),
+        };
+
+        assert!(accounts.users().is_err());
+        assert!(accounts.groups().is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::path::Path;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::checks::compare;
+use crate::test_suite::OneOrMany;
+
+/// Checks a file or folder without needing a shell. Only the checks that are set are made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileCheck {
+    pub path: String,
+    pub exists: Option<bool>,
+    #[serde(rename = "type")]
+    pub file_type: Option<FileType>,
+    /// Permission bits in octal, for example `0644`.
+    pub mode: Option<String>,
+    /// User name or uid.
+    pub owner: Option<String>,
+    /// Group name or gid.
+    pub group: Option<String>,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    pub content_matches: Option<OneOrMany>,
+    pub contains_line: Option<OneOrMany>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl FileCheck {
+    pub fn run(&self, base: &Path) -> Vec<(String, bool)> {
+        let mut result = vec![];
+        let path = base.join(&self.path);
+        let name = &self.path;
+
+        let link_meta = match fs::symlink_metadata(&path) {
+            Ok(meta) => Some(meta),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                result.push((format!("{name} exists\n  {e}"), false));
+                return result;
+            }
+        };
+
+        if self.exists == Some(false) {
+            result.push((format!("{name} does not exist"), link_meta.is_none()));
+            return result;
+        }
+
+        let Some(link_meta) = link_meta else {
+            result.push((format!("{name} exists"), false));
+            return result;
+        };
+
+        result.push((format!("{name} exists"), true));
+
+        if let Some(expected) = &self.file_type {
+            let actual = if link_meta.file_type().is_symlink() {
+                FileType::Symlink
+            } else if link_meta.is_dir() {
+                FileType::Directory
+            } else {
+                FileType::File
+            };
+
+            result.push(compare(format!("{name} is a {expected:?}").to_lowercase(), expected == &actual, format!("{actual:?}").to_lowercase()));
+        }
+
+        //Everything else is about what a symlink points to.
+        let meta = match fs::metadata(&path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                result.push((format!("{name} can be read\n  {e}"), false));
+                return result;
+            }
+        };
+
+        if let Some(expected) = &self.mode {
+            result.push(check_mode(name, expected, &meta));
+        }
+
+        if let Some(expected) = &self.owner {
+            result.push(check_owner(name, expected, &meta));
+        }
+
+        if let Some(expected) = &self.group {
+            result.push(check_group(name, expected, &meta));
+        }
+
+        if let Some(expected) = self.size {
+            result.push(compare(format!("{name} is {expected} bytes"), meta.len() == expected, format!("{} bytes", meta.len())));
+        }
+
+        if let Some(expected) = &self.sha256 {
+            let message = format!("{name} has sha256 {expected}");
+
+            match fs::read(&path) {
+                Ok(data) => {
+                    let actual = format!("{:x}", Sha256::digest(&data));
+                    result.push(compare(message, actual == expected.trim().to_lowercase(), actual));
+                }
+                Err(e) => result.push((format!("{message}\n  {e}"), false)),
+            }
+        }
+
+        if self.content_matches.is_none() && self.contains_line.is_none() {
+            return result;
+        }
+
+        let content = match fs::read(&path) {
+            Ok(data) => String::from_utf8_lossy(&data).to_string(),
+            Err(e) => {
+                result.push((format!("{name} can be read\n  {e}"), false));
+                return result;
+            }
+        };
+
+        if let Some(patterns) = &self.content_matches {
+            for pattern in patterns.items() {
+                let message = format!("{name} matches /{pattern}/");
+
+                match Regex::new(pattern) {
+                    Ok(re) => result.push((message, re.is_match(&content))),
+                    Err(e) => result.push((format!("{message}\n  invalid regular expression: {e}"), false)),
+                }
+            }
+        }
+
+        if let Some(lines) = &self.contains_line {
+            for line in lines.items() {
+                let found = content.lines().any(|l| l.trim_end_matches('\r') == line);
+                result.push((format!("{name} contains line \"{line}\""), found));
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(unix)]
+fn check_mode(name: &str, expected: &str, meta: &Metadata) -> (String, bool) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let message = format!("{name} has mode {expected}");
+    let actual = meta.permissions().mode() & 0o7777;
+
+    match u32::from_str_radix(expected.trim().trim_start_matches("0o"), 8) {
+        Ok(mode) => compare(message, mode == actual, format!("{actual:04o}")),
+        Err(_) => (format!("{message}\n  {expected} isn't an octal mode"), false),
+    }
+}
+
+#[cfg(unix)]
+fn check_owner(name: &str, expected: &str, meta: &Metadata) -> (String, bool) {
+    use std::os::unix::fs::MetadataExt;
+    use crate::checks::accounts::user_name;
+
+    let actual = user_name(meta.uid());
+    let success = match expected.parse::<u32>() {
+        Ok(uid) => uid == meta.uid(),
+        Err(_) => actual == expected,
+    };
+
+    compare(format!("{name} is owned by {expected}"), success, format!("{actual} ({})", meta.uid()))
+}
+
+#[cfg(unix)]
+fn check_group(name: &str, expected: &str, meta: &Metadata) -> (String, bool) {
+    use std::os::unix::fs::MetadataExt;
+    use crate::checks::accounts::group_name;
+
+    let actual = group_name(meta.gid());
+    let success = match expected.parse::<u32>() {
+        Ok(gid) => gid == meta.gid(),
+        Err(_) => actual == expected,
+    };
+
+    compare(format!("{name} has group {expected}"), success, format!("{actual} ({})", meta.gid()))
+}
+
+#[cfg(not(unix))]
+fn check_mode(name: &str, expected: &str, _meta: &Metadata) -> (String, bool) {
+    (format!("{name} has mode {expected}\n  file modes aren't supported on this platform"), false)
+}
+
+#[cfg(not(unix))]
+fn check_owner(name: &str, expected: &str, _meta: &Metadata) -> (String, bool) {
+    (format!("{name} is owned by {expected}\n  file owners aren't supported on this platform"), false)
+}
+
+#[cfg(not(unix))]
+fn check_group(name: &str, expected: &str, _meta: &Metadata) -> (String, bool) {
+    (format!("{name} has group {expected}\n  file groups aren't supported on this platform"), false)
+}
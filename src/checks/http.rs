@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use reqwest::{Certificate, Client, Method, Response};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use crate::checks::{compare, deadline, retry_after};
+use crate::expect::evaluate_json;
+use crate::test_suite::{HumanDuration, JsonExpectation, OneOrMany};
+
+/// How long to wait between attempts while waiting for a service to come up.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Makes a HTTP request and checks the response without needing curl or jq. Only the checks that are set are made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpCheck {
+    pub url: String,
+    pub method: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub body: Option<String>,
+    /// Status code the response must have, defaults to any 2xx status.
+    pub status: Option<u16>,
+    /// Regular expressions the named response headers must match.
+    pub response_headers: Option<HashMap<String, String>>,
+    pub body_matches: Option<OneOrMany>,
+    pub json: Option<Vec<JsonExpectation>>,
+    /// Longest the response can take, like `500ms`.
+    pub max_response_time: Option<HumanDuration>,
+    /// Accept any TLS certificate, for services with self signed certificates.
+    pub insecure: Option<bool>,
+    /// PEM file with an extra CA certificate to trust.
+    pub ca_cert: Option<String>,
+    /// Times to try again if the service can't be reached or returns a 5xx status.
+    pub retries: Option<u32>,
+}
+
+struct Reply {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: String,
+    elapsed: Duration,
+}
+
+impl HttpCheck {
+    pub async fn run(&self, base: &Path, time_out: Duration) -> Vec<(String, bool)> {
+        let deadline = deadline(time_out);
+        let method = self.method.clone().unwrap_or("GET".to_string()).to_uppercase();
+        let name = format!("{method} {}", self.url);
+
+        let client = match self.client(base) {
+            Ok(client) => client,
+            Err(e) => return vec![(format!("{name} can be sent\n  {e}"), false)],
+        };
+
+        let method = match Method::from_bytes(method.as_bytes()) {
+            Ok(method) => method,
+            Err(_) => return vec![(format!("{name} can be sent\n  {method} isn't a HTTP method"), false)],
+        };
+
+        let mut attempts_left = self.retries.unwrap_or(0);
+
+        //Keep trying while the service is starting, but only as many times as asked and within the step's timeout.
+        let reply = loop {
+            let result = self.send(&client, method.clone(), deadline).await;
+            let retry = match &result {
+                Ok(reply) => reply.status >= 500,
+                Err(_) => true,
+            };
+
+            if !retry || attempts_left == 0 || !retry_after(RETRY_INTERVAL, deadline).await {
+                break result;
+            }
+
+            attempts_left -= 1;
+        };
+
+        match reply {
+            Ok(reply) => self.check(&name, &reply),
+            Err(e) => vec![(format!("{name} gets a response\n  {e:#}"), false)],
+        }
+    }
+
+    fn client(&self, base: &Path) -> Result<Client> {
+        let mut builder = Client::builder()
+            .danger_accept_invalid_certs(self.insecure.unwrap_or(false));
+
+        if let Some(ca_cert) = &self.ca_cert {
+            let pem = fs::read(base.join(ca_cert))
+                .map_err(|e| anyhow!("Can't read CA certificate {ca_cert}: {e}"))?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    async fn send(&self, client: &Client, method: Method, deadline: Instant) -> Result<Reply> {
+        let mut request = client.request(method, &self.url)
+            .timeout(deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1)));
+
+        for (key, value) in self.headers.iter().flatten() {
+            request = request.header(key, value);
+        }
+
+        if let Some(body) = &self.body {
+            request = request.body(body.clone());
+        }
+
+        let started = Instant::now();
+        let response: Response = request.send().await?;
+        let elapsed = started.elapsed();
+        let status = response.status().as_u16();
+        let headers = response.headers().iter()
+            .map(|(k, v)| (k.as_str().to_lowercase(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+            .collect();
+        let body = response.text().await?;
+
+        Ok(Reply { status, headers, body, elapsed })
+    }
+
+    fn check(&self, name: &str, reply: &Reply) -> Vec<(String, bool)> {
+        let mut result = vec![];
+
+        let (message, success) = match self.status {
+            Some(status) => (format!("{name} returns status {status}"), reply.status == status),
+            None => (format!("{name} returns a 2xx status"), (200..300).contains(&reply.status)),
+        };
+
+        result.push(compare(message, success, reply.status.to_string()));
+
+        for (header, pattern) in self.response_headers.iter().flatten() {
+            let message = format!("header {header} matches /{pattern}/");
+            let actual = reply.headers.get(&header.to_lowercase());
+
+            match (Regex::new(pattern), actual) {
+                (Err(e), _) => result.push((format!("{message}\n  invalid regular expression: {e}"), false)),
+                (Ok(_), None) => result.push((format!("{message}\n  header is missing"), false)),
+                (Ok(re), Some(actual)) => result.push(compare(message, re.is_match(actual), actual.clone())),
+            }
+        }
+
+        if let Some(patterns) = &self.body_matches {
+            for pattern in patterns.items() {
+                let message = format!("body matches /{pattern}/");
+
+                match Regex::new(pattern) {
+                    Ok(re) => result.push((message, re.is_match(&reply.body))),
+                    Err(e) => result.push((format!("{message}\n  invalid regular expression: {e}"), false)),
+                }
+            }
+        }
+
+        if let Some(items) = &self.json {
+            result.extend(evaluate_json(items, &reply.body));
+        }
+
+        if let Some(limit) = self.max_response_time {
+            let elapsed = reply.elapsed.as_millis();
+            result.push(compare(format!("response took at most {limit}"), reply.elapsed <= limit.0, format!("{elapsed}ms")));
+        }
+
+        result
+    }
+}
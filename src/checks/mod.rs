@@ -0,0 +1,122 @@
+//! Built in step types that check the state of the machine without running a command.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_yaml::Value;
+use tokio::time::{sleep, Instant};
+use crate::environment::interpolate;
+use crate::test_suite::TestStep;
+
+pub mod accounts;
+pub mod file;
+pub mod http;
+pub mod package;
+pub mod port;
+pub mod process;
+pub mod service;
+pub mod user;
+pub mod version;
+
+/// Runs the built in check a step has. Returns `None` if the step doesn't have one.
+/// `${NAME}` in the check is replaced with the step's variables first, data set row included.
+pub async fn run(step: &TestStep, base: &Path, time_out: Duration, env: &HashMap<String, String>) -> Option<Vec<(String, bool)>> {
+    if let Some(file) = &step.file {
+        return Some(with_env(file, env).run(base));
+    }
+
+    if let Some(port) = &step.port {
+        return Some(with_env(port, env).run(time_out).await);
+    }
+
+    if let Some(http) = &step.http {
+        return Some(with_env(http, env).run(base, time_out).await);
+    }
+
+    if let Some(process) = &step.process {
+        return Some(with_env(process, env).run());
+    }
+
+    if let Some(service) = &step.service {
+        return Some(with_env(service, env).run().await);
+    }
+
+    if let Some(user) = &step.user {
+        return Some(with_env(user, env).run());
+    }
+
+    if let Some(group) = &step.group {
+        return Some(with_env(group, env).run());
+    }
+
+    if let Some(package) = &step.package {
+        return Some(with_env(package, env).run().await);
+    }
+
+    None
+}
+
+/// A copy of the check with the variables in all of its text replaced.
+fn with_env<T: Serialize + DeserializeOwned + Clone>(check: &T, env: &HashMap<String, String>) -> T {
+    serde_yaml::to_value(check)
+        .and_then(|value| serde_yaml::from_value(interpolate_value(value, env)))
+        .unwrap_or(check.clone())
+}
+
+fn interpolate_value(value: Value, env: &HashMap<String, String>) -> Value {
+    match value {
+        Value::String(text) => Value::String(interpolate(&text, env)),
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(|v| interpolate_value(v, env)).collect()),
+        Value::Mapping(map) => Value::Mapping(map.into_iter().map(|(k, v)| (k, interpolate_value(v, env))).collect()),
+        value => value,
+    }
+}
+
+/// A check's result, with what was actually found added to the message when it failed.
+fn compare(message: String, success: bool, actual: String) -> (String, bool) {
+    if success || actual.is_empty() {
+        (message, success)
+    } else {
+        (format!("{message}\n  actual: {actual}"), false)
+    }
+}
+
+/// When a check that keeps trying has to give up, `time_out` from now.
+fn deadline(time_out: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(time_out).unwrap_or(now + Duration::from_secs(u32::MAX as u64))
+}
+
+/// Waits `interval` before a check tries again. Returns false straight away if that would take it past `deadline`.
+async fn retry_after(interval: Duration, deadline: Instant) -> bool {
+    if Instant::now() + interval >= deadline {
+        return false;
+    }
+
+    sleep(interval).await;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::file::FileCheck;
+
+    #[test]
+    fn checks_use_the_step_variables() {
+        let check: FileCheck = serde_yaml::from_str("path: ${DIR}/${F}\ncontains_line: [\"host ${HOST}\", \"${MISSING}\"]\nsize: 10").unwrap();
+        let env = HashMap::from([
+            ("DIR".to_string(), "/etc".to_string()),
+            ("F".to_string(), "hosts".to_string()),
+            ("HOST".to_string(), "web01".to_string()),
+        ]);
+
+        let check = with_env(&check, &env);
+
+        assert_eq!(check.path, "/etc/hosts");
+        assert_eq!(check.size, Some(10));
+        assert_eq!(check.contains_line.unwrap().items(), vec!["host web01", "${MISSING}"]);
+    }
+}
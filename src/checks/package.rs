@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use crate::checks::version::{satisfies, Scheme};
+
+/// Where dpkg keeps the state of every package it knows about.
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+
+/// Checks a package is installed, or not, using the dpkg status database or rpm.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PackageCheck {
+    /// Package name, with an architecture if needed such as `libc6:amd64`.
+    pub name: String,
+    pub installed: Option<bool>,
+    /// Version constraints like `>= 1.18, < 2`, or a version that has to match exactly.
+    pub version: Option<String>,
+}
+
+impl PackageCheck {
+    pub async fn run(&self) -> Vec<(String, bool)> {
+        let name = format!("package {}", self.name);
+
+        let (scheme, versions) = match installed_versions(&self.name).await {
+            Ok(found) => found,
+            Err(e) => return vec![(format!("{name} can be looked up\n  {e}"), false)],
+        };
+
+        if self.installed == Some(false) {
+            let message = format!("{name} is not installed");
+
+            return match versions.is_empty() {
+                true => vec![(message, true)],
+                false => vec![(format!("{message}\n  installed: {}", versions.join(", ")), false)],
+            };
+        }
+
+        let Some(constraint) = &self.version else {
+            return vec![(format!("{name} is installed"), !versions.is_empty())];
+        };
+
+        let message = format!("{name} is installed at version {constraint}");
+
+        if versions.is_empty() {
+            return vec![(format!("{message}\n  not installed"), false)];
+        }
+
+        let mut success = false;
+
+        for version in &versions {
+            match satisfies(scheme, version, constraint) {
+                Ok(matches) => success |= matches,
+                Err(e) => return vec![(format!("{message}\n  {e}"), false)],
+            }
+        }
+
+        if success {
+            vec![(message, true)]
+        } else {
+            vec![(format!("{message}\n  installed: {}", versions.join(", ")), false)]
+        }
+    }
+}
+
+/// Versions of the package that are installed, using dpkg if the machine has it otherwise rpm.
+async fn installed_versions(name: &str) -> Result<(Scheme, Vec<String>)> {
+    if Path::new(DPKG_STATUS).exists() {
+        let text = fs::read_to_string(DPKG_STATUS)?;
+        return Ok((Scheme::Dpkg, dpkg_versions(&text, name)));
+    }
+
+    let output = Command::new("rpm")
+        .args(["-q", "--queryformat", "%|EPOCH?{%{EPOCH}:}:{}|%{VERSION}-%{RELEASE}\\n", name])
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() => {
+            Ok((Scheme::Rpm, String::from_utf8_lossy(&output.stdout).lines().map(|l| l.trim().to_string()).collect()))
+        }
+        //rpm exits with 1 and says so on stdout when the package isn't installed.
+        Ok(output) if String::from_utf8_lossy(&output.stdout).contains("is not installed") => Ok((Scheme::Rpm, vec![])),
+        Ok(output) => bail!("rpm: {}", String::from_utf8_lossy(&output.stderr).trim_end()),
+        Err(_) => bail!("No package database found, only dpkg and rpm are supported"),
+    }
+}
+
+/// Reads the stanzas of the dpkg status file for the installed versions of a package.
+fn dpkg_versions(text: &str, name: &str) -> Vec<String> {
+    let mut result = vec![];
+
+    for stanza in text.split("\n\n") {
+        let field = |key: &str| stanza.lines()
+            .find_map(|l| l.strip_prefix(key).and_then(|v| v.strip_prefix(':')))
+            .map(|v| v.trim())
+            .unwrap_or_default();
+
+        let package = field("Package");
+        let with_arch = format!("{package}:{}", field("Architecture"));
+
+        if (package == name || with_arch == name) && field("Status").ends_with(" installed") {
+            result.push(field("Version").to_string());
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "\
+Package: openssl
+Status: install ok installed
+Architecture: amd64
+Version: 3.0.2-0ubuntu1.15
+
+Package: libc6
+Status: install ok installed
+Architecture: amd64
+Version: 2.35-0ubuntu3.6
+Description: GNU C Library
+ Contains the standard libraries.
+
+Package: libc6
+Status: install ok installed
+Architecture: i386
+Version: 2.35-0ubuntu3.6
+
+Package: nginx
+Status: deinstall ok config-files
+Architecture: amd64
+Version: 1.18.0-6ubuntu14
+";
+
+    #[test]
+    fn installed_package() {
+        assert_eq!(dpkg_versions(STATUS, "openssl"), vec!["3.0.2-0ubuntu1.15"]);
+    }
+
+    #[test]
+    fn package_for_each_architecture() {
+        assert_eq!(dpkg_versions(STATUS, "libc6").len(), 2);
+        assert_eq!(dpkg_versions(STATUS, "libc6:i386"), vec!["2.35-0ubuntu3.6"]);
+    }
+
+    #[test]
+    fn removed_or_unknown_package() {
+        assert!(dpkg_versions(STATUS, "nginx").is_empty());
+        assert!(dpkg_versions(STATUS, "apache2").is_empty());
+        assert!(dpkg_versions(STATUS, "openssl:arm64").is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::ErrorKind;
+use std::time::Duration;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::task::spawn_blocking;
+use tokio::time::{timeout, Instant};
+use crate::checks::{deadline, retry_after};
+
+/// How long to wait between attempts while waiting for a port to change state.
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Longest a single connection attempt can take, so a dropped packet doesn't use up the whole timeout.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Checks a local or remote port is listening, or not, within the step's timeout.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PortCheck {
+    pub host: Option<String>,
+    pub port: u16,
+    pub protocol: Option<PortProtocol>,
+    pub listening: Option<bool>,
+    /// Regular expression the greeting a TCP service sends when connected to must match.
+    pub banner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PortProtocol {
+    Tcp,
+    Udp,
+}
+
+enum Probe {
+    Open(Option<TcpStream>),
+    Closed(String),
+}
+
+impl PortCheck {
+    pub async fn run(&self, time_out: Duration) -> Vec<(String, bool)> {
+        let deadline = deadline(time_out);
+        let host = self.host.clone().unwrap_or("127.0.0.1".to_string());
+        let protocol = self.protocol.clone().unwrap_or(PortProtocol::Tcp);
+        let listening = self.listening.unwrap_or(true);
+        let name = format!("{}/{host}:{}", format!("{protocol:?}").to_lowercase(), self.port);
+
+        let banner = match self.banner.as_ref().map(|b| Regex::new(b)).transpose() {
+            Ok(banner) => banner,
+            Err(e) => return vec![(format!("{name} banner is valid\n  invalid regular expression: {e}"), false)],
+        };
+
+        //Keep trying until the port is in the state we want or we run out of time.
+        loop {
+            let probe = match protocol {
+                PortProtocol::Tcp => probe_tcp(&host, self.port, deadline).await,
+                PortProtocol::Udp => probe_udp(&host, self.port, deadline).await,
+            };
+
+            match (probe, listening) {
+                (Probe::Open(stream), true) => {
+                    let mut result = vec![(format!("{name} is listening"), true)];
+
+                    if let (Some(banner), Some(stream)) = (&banner, stream) {
+                        result.push(check_banner(&name, banner, stream, deadline).await);
+                    }
+
+                    return result;
+                }
+                (Probe::Closed(_), false) => {
+                    return vec![(format!("{name} is not listening"), true)];
+                }
+                (Probe::Open(_), false) => {
+                    if !retry_after(RETRY_INTERVAL, deadline).await {
+                        return vec![(format!("{name} is not listening\n  still accepting connections"), false)];
+                    }
+                }
+                (Probe::Closed(reason), true) => {
+                    if !retry_after(RETRY_INTERVAL, deadline).await {
+                        return vec![(format!("{name} is listening\n  {reason}"), false)];
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn probe_tcp(host: &str, port: u16, deadline: Instant) -> Probe {
+    let attempt = ATTEMPT_TIMEOUT.min(deadline.saturating_duration_since(Instant::now()));
+
+    match timeout(attempt, TcpStream::connect((host, port))).await {
+        Ok(Ok(stream)) => Probe::Open(Some(stream)),
+        Ok(Err(e)) => Probe::Closed(e.to_string()),
+        Err(_) => Probe::Closed("connection attempt timed out".to_string()),
+    }
+}
+
+/// UDP has no handshake, so a port counts as closed only if the host tells us so (ICMP port unreachable).
+/// A port that is firewalled and drops the probe is reported as listening.
+async fn probe_udp(host: &str, port: u16, deadline: Instant) -> Probe {
+    let attempt = Duration::from_millis(500).min(deadline.saturating_duration_since(Instant::now()));
+    let host = host.to_string();
+
+    //The refusal only shows up as an error on a blocking read, so this runs off the async threads.
+    let probe = spawn_blocking(move || -> std::io::Result<()> {
+        let bind = if host.contains(':') { "[::]:0" } else { "0.0.0.0:0" };
+        let socket = std::net::UdpSocket::bind(bind)?;
+
+        socket.connect((host.as_str(), port))?;
+        socket.set_read_timeout(Some(attempt.max(Duration::from_millis(1))))?;
+        socket.send(&[])?;
+
+        let mut buffer = [0u8; 512];
+
+        match socket.recv(&mut buffer) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => Ok(()),
+            Err(e) => Err(e),
+            Ok(_) => Ok(()),
+        }
+    }).await;
+
+    match probe {
+        Ok(Ok(())) => Probe::Open(None),
+        Ok(Err(e)) => Probe::Closed(e.to_string()),
+        Err(e) => Probe::Closed(e.to_string()),
+    }
+}
+
+async fn check_banner(name: &str, banner: &Regex, mut stream: TcpStream, deadline: Instant) -> (String, bool) {
+    let message = format!("{name} banner matches /{}/", banner.as_str());
+    let mut received: Vec<u8> = vec![];
+    let mut buffer = [0u8; 1024];
+
+    loop {
+        let text = String::from_utf8_lossy(&received).to_string();
+
+        if banner.is_match(&text) {
+            return (message, true);
+        }
+
+        let read = timeout(deadline.saturating_duration_since(Instant::now()), stream.read(&mut buffer)).await;
+
+        match read {
+            Ok(Ok(count)) if count > 0 => received.extend_from_slice(&buffer[..count]),
+            _ => return (format!("{message}\n  received: {}", text.trim_end()), false),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::checks::compare;
+
+/// Checks a process is running, or not, by its name or command line. Only the checks that are set are made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessCheck {
+    /// Exact name of the executable, for example `nginx`.
+    pub name: Option<String>,
+    /// Regular expression the full command line must match.
+    pub cmdline: Option<String>,
+    pub running: Option<bool>,
+    /// Number of matching processes there must be.
+    pub count: Option<usize>,
+    /// User name or uid all matching processes must run as.
+    pub user: Option<String>,
+}
+
+/// A process running on the machine.
+pub struct Process {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+    pub user: String,
+    pub uid: Option<u32>,
+}
+
+impl ProcessCheck {
+    pub fn run(&self) -> Vec<(String, bool)> {
+        let mut result = vec![];
+        let name = match (&self.name, &self.cmdline) {
+            (Some(name), _) => format!("process {name}"),
+            (None, Some(cmdline)) => format!("process matching /{cmdline}/"),
+            (None, None) => return vec![("process has a name or cmdline to look for".to_string(), false)],
+        };
+
+        let cmdline = match self.cmdline.as_ref().map(|c| Regex::new(c)).transpose() {
+            Ok(cmdline) => cmdline,
+            Err(e) => return vec![(format!("{name} can be looked for\n  invalid regular expression: {e}"), false)],
+        };
+
+        let processes = match processes() {
+            Ok(processes) => processes,
+            Err(e) => return vec![(format!("{name} can be looked for\n  {e}"), false)],
+        };
+
+        //Weave test's own command line can match the pattern, so it is never counted.
+        let own_pid = std::process::id();
+        let matching: Vec<Process> = processes.into_iter()
+            .filter(|p| p.pid != own_pid)
+            .filter(|p| self.name.as_ref().map(|n| &p.name == n).unwrap_or(true))
+            .filter(|p| cmdline.as_ref().map(|re| re.is_match(&p.cmdline)).unwrap_or(true))
+            .collect();
+
+        if self.running == Some(false) {
+            let pids: Vec<String> = matching.iter().map(|p| p.pid.to_string()).collect();
+            result.push(compare(format!("{name} is not running"), matching.is_empty(), format!("running as pid {}", pids.join(", "))));
+            return result;
+        }
+
+        if self.count.is_none() || self.running == Some(true) {
+            result.push((format!("{name} is running"), !matching.is_empty()));
+        }
+
+        if let Some(expected) = self.count {
+            result.push(compare(format!("{name} has {expected} instances"), matching.len() == expected, matching.len().to_string()));
+        }
+
+        if let Some(expected) = &self.user {
+            let others: Vec<String> = matching.iter()
+                .filter(|p| match expected.parse::<u32>() {
+                    Ok(uid) => p.uid != Some(uid),
+                    Err(_) => &p.user != expected,
+                })
+                .map(|p| format!("{} (pid {})", p.user, p.pid))
+                .collect();
+
+            result.push(compare(format!("{name} runs as {expected}"), !matching.is_empty() && others.is_empty(), others.join(", ")));
+        }
+
+        result
+    }
+}
+
+/// Lists the running processes from `/proc`.
+#[cfg(target_os = "linux")]
+pub fn processes() -> Result<Vec<Process>> {
+    use std::fs;
+    use crate::checks::accounts::users;
+
+    let users = users().unwrap_or_default();
+    let mut result = vec![];
+
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+
+        //Processes can exit while we are reading them.
+        let Ok(status) = fs::read_to_string(entry.path().join("status")) else {
+            continue;
+        };
+
+        let comm = fs::read_to_string(entry.path().join("comm")).unwrap_or_default().trim_end().to_string();
+        let args = fs::read(entry.path().join("cmdline")).unwrap_or_default();
+        let args = String::from_utf8_lossy(&args);
+        let cmdline = args.trim_end_matches('\0').replace('\0', " ");
+
+        let exe = fs::read_link(entry.path().join("exe")).ok()
+            .and_then(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_default();
+        let argv0 = args.split('\0').next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+        let name = process_name(&comm, &[&exe, argv0]);
+        let uid = status.lines()
+            .find_map(|l| l.strip_prefix("Uid:"))
+            .and_then(|l| l.split_whitespace().next())
+            .and_then(|uid| uid.parse::<u32>().ok());
+
+        result.push(Process {
+            pid,
+            name,
+            cmdline,
+            user: uid.map(|uid| users.iter().find(|u| u.uid == uid).map(|u| u.name.clone()).unwrap_or(uid.to_string())).unwrap_or_default(),
+            uid,
+        });
+    }
+
+    Ok(result)
+}
+
+/// The kernel cuts `comm` down to 15 characters. Only then is a longer name that starts with it taken from the
+/// executable or argv[0], which the process can rewrite to anything, like nginx's `nginx: worker process`.
+#[cfg(any(target_os = "linux", test))]
+fn process_name(comm: &str, longer: &[&str]) -> String {
+    if comm.chars().count() == 15 {
+        if let Some(name) = longer.iter().find(|n| n.len() > comm.len() && n.starts_with(comm)) {
+            return name.to_string();
+        }
+    }
+
+    comm.to_string()
+}
+
+/// Lists the running processes with `ps`.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn processes() -> Result<Vec<Process>> {
+    use std::path::Path;
+    use std::process::Command;
+
+    let output = Command::new("ps").args(["-A", "-o", "pid=,uid=,user=,comm=,args="]).output()?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let uid = fields.next()?.parse().ok();
+            let user = fields.next()?.to_string();
+            let comm = fields.next()?;
+            let name = Path::new(comm).file_name()?.to_string_lossy().to_string();
+
+            Some(Process {
+                pid,
+                name,
+                cmdline: fields.collect::<Vec<&str>>().join(" "),
+                user,
+                uid,
+            })
+        })
+        .collect())
+}
+
+/// Lists the running processes with `tasklist`. The command line isn't available, so the image name is used instead.
+#[cfg(windows)]
+pub fn processes() -> Result<Vec<Process>> {
+    use std::process::Command;
+
+    let output = Command::new("tasklist").args(["/FO", "CSV", "/NH", "/V"]).output()?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.trim().trim_matches('"').split("\",\"").collect();
+            let image = fields.first()?.to_string();
+            let user = fields.get(6)?.rsplit('\\').next()?.to_string();
+
+            Some(Process {
+                pid: fields.get(1)?.parse().ok()?,
+                name: image.trim_end_matches(".exe").to_string(),
+                cmdline: image,
+                user,
+                uid: None,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comm_is_the_name() {
+        assert_eq!(process_name("sleep", &["sleep", "sleep: worker"]), "sleep");
+        assert_eq!(process_name("nginx", &["nginx", "nginx: worker process"]), "nginx");
+        assert_eq!(process_name("sleep", &["", "sleep: worker"]), "sleep");
+    }
+
+    #[test]
+    fn truncated_comm_uses_a_longer_name() {
+        assert_eq!(process_name("systemd-resolve", &["systemd-resolved", ""]), "systemd-resolved");
+        assert_eq!(process_name("systemd-resolve", &["", "systemd-resolved"]), "systemd-resolved");
+        assert_eq!(process_name("systemd-resolve", &["python3", "other"]), "systemd-resolve");
+    }
+}
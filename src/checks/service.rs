@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use crate::checks::compare;
+
+/// Checks the state of a systemd unit using `systemctl show`. Only the checks that are set are made,
+/// if none are the unit has to be active.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceCheck {
+    pub name: String,
+    pub active: Option<bool>,
+    /// Expected `ActiveState`, for example `active`, `failed` or `inactive`.
+    pub state: Option<String>,
+    /// Expected `SubState`, for example `running` or `exited`.
+    pub sub_state: Option<String>,
+    /// Whether the unit starts at boot.
+    pub enabled: Option<bool>,
+}
+
+impl ServiceCheck {
+    pub async fn run(&self) -> Vec<(String, bool)> {
+        let mut result = vec![];
+        let name = format!("service {}", self.name);
+
+        let output = Command::new("systemctl")
+            .args(["show", &self.name, "--no-pager", "--property=LoadState,ActiveState,SubState,UnitFileState"])
+            .output()
+            .await;
+
+        let properties = match output {
+            Ok(output) if output.status.success() => parse_show(&String::from_utf8_lossy(&output.stdout)),
+            Ok(output) => return vec![(format!("{name} can be queried\n  {}", String::from_utf8_lossy(&output.stderr).trim_end().replace('\n', "\n  ")), false)],
+            Err(e) => return vec![(format!("{name} can be queried\n  systemctl: {e}"), false)],
+        };
+
+        let property = |key: &str| properties.get(key).cloned().unwrap_or_default();
+        let active_state = property("ActiveState");
+
+        if property("LoadState") == "not-found" {
+            if self.active == Some(false) && self.state.is_none() && self.sub_state.is_none() && self.enabled.is_none() {
+                result.push((format!("{name} is not active"), true));
+            } else {
+                result.push((format!("{name} exists"), false));
+            }
+
+            return result;
+        }
+
+        let active = match self.active {
+            None if self.state.is_none() && self.sub_state.is_none() && self.enabled.is_none() => Some(true),
+            active => active,
+        };
+
+        if let Some(expected) = active {
+            let message = if expected { format!("{name} is active") } else { format!("{name} is not active") };
+            result.push(compare(message, (active_state == "active") == expected, active_state.clone()));
+        }
+
+        if let Some(expected) = &self.state {
+            result.push(compare(format!("{name} is {expected}"), &active_state == expected, active_state.clone()));
+        }
+
+        if let Some(expected) = &self.sub_state {
+            let actual = property("SubState");
+            result.push(compare(format!("{name} is {expected}"), &actual == expected, actual));
+        }
+
+        if let Some(expected) = self.enabled {
+            let actual = property("UnitFileState");
+            let message = if expected { format!("{name} is enabled") } else { format!("{name} is not enabled") };
+            result.push(compare(message, actual.starts_with("enabled") == expected, actual));
+        }
+
+        result
+    }
+}
+
+/// Reads the `Key=Value` lines `systemctl show` prints.
+fn parse_show(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use crate::checks::accounts::{source, AccountSource, Group, User};
+use crate::checks::compare;
+use crate::test_suite::OneOrMany;
+
+/// Checks a local user account. Only the checks that are set are made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserCheck {
+    pub name: String,
+    pub exists: Option<bool>,
+    pub uid: Option<u32>,
+    /// Primary group name or gid.
+    pub group: Option<String>,
+    pub home: Option<String>,
+    pub shell: Option<String>,
+    /// Groups the user has to be a member of, including its primary group.
+    pub groups: Option<OneOrMany>,
+}
+
+/// Checks a local group. Only the checks that are set are made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupCheck {
+    pub name: String,
+    pub exists: Option<bool>,
+    pub gid: Option<u32>,
+    /// Users that have to be members of the group.
+    pub members: Option<OneOrMany>,
+}
+
+impl UserCheck {
+    pub fn run(&self) -> Vec<(String, bool)> {
+        self.run_with(source().as_ref())
+    }
+
+    fn run_with(&self, accounts: &dyn AccountSource) -> Vec<(String, bool)> {
+        let mut result = vec![];
+        let name = format!("user {}", self.name);
+
+        let (users, groups) = match (accounts.users(), accounts.groups()) {
+            (Ok(users), Ok(groups)) => (users, groups),
+            (Err(e), _) | (_, Err(e)) => return vec![(format!("{name} can be looked up\n  {e}"), false)],
+        };
+
+        let user = users.into_iter().find(|u| u.name == self.name);
+
+        if self.exists == Some(false) {
+            result.push((format!("{name} does not exist"), user.is_none()));
+            return result;
+        }
+
+        let Some(user) = user else {
+            result.push((format!("{name} exists"), false));
+            return result;
+        };
+
+        result.push((format!("{name} exists"), true));
+
+        if let Some(expected) = self.uid {
+            result.push(compare(format!("{name} has uid {expected}"), user.uid == expected, user.uid.to_string()));
+        }
+
+        if let Some(expected) = &self.group {
+            let actual = groups.iter().find(|g| g.gid == user.gid).map(|g| g.name.clone()).unwrap_or(user.gid.to_string());
+            let success = match expected.parse::<u32>() {
+                Ok(gid) => gid == user.gid,
+                Err(_) => &actual == expected,
+            };
+
+            result.push(compare(format!("{name} has primary group {expected}"), success, format!("{actual} ({})", user.gid)));
+        }
+
+        if let Some(expected) = &self.home {
+            result.push(compare(format!("{name} has home {expected}"), &user.home == expected, user.home.clone()));
+        }
+
+        if let Some(expected) = &self.shell {
+            result.push(compare(format!("{name} has shell {expected}"), &user.shell == expected, user.shell.clone()));
+        }
+
+        if let Some(expected) = &self.groups {
+            let actual = member_of(&user, &groups);
+
+            for group in expected.items() {
+                result.push(compare(format!("{name} is a member of {group}"), actual.contains(group), actual.join(", ")));
+            }
+        }
+
+        result
+    }
+}
+
+impl GroupCheck {
+    pub fn run(&self) -> Vec<(String, bool)> {
+        self.run_with(source().as_ref())
+    }
+
+    fn run_with(&self, accounts: &dyn AccountSource) -> Vec<(String, bool)> {
+        let mut result = vec![];
+        let name = format!("group {}", self.name);
+
+        let (users, groups) = match (accounts.users(), accounts.groups()) {
+            (Ok(users), Ok(groups)) => (users, groups),
+            (Err(e), _) | (_, Err(e)) => return vec![(format!("{name} can be looked up\n  {e}"), false)],
+        };
+
+        let group = groups.iter().find(|g| g.name == self.name);
+
+        if self.exists == Some(false) {
+            result.push((format!("{name} does not exist"), group.is_none()));
+            return result;
+        }
+
+        let Some(group) = group else {
+            result.push((format!("{name} exists"), false));
+            return result;
+        };
+
+        result.push((format!("{name} exists"), true));
+
+        if let Some(expected) = self.gid {
+            result.push(compare(format!("{name} has gid {expected}"), group.gid == expected, group.gid.to_string()));
+        }
+
+        if let Some(expected) = &self.members {
+            //Users whose primary group this is are members without being listed.
+            let mut actual = group.members.clone();
+            actual.extend(users.iter().filter(|u| u.gid == group.gid && !group.members.contains(&u.name)).map(|u| u.name.clone()));
+
+            for member in expected.items() {
+                result.push(compare(format!("{name} has member {member}"), actual.contains(member), actual.join(", ")));
+            }
+        }
+
+        result
+    }
+}
+
+/// Names of the groups a user is in, starting with their primary group.
+fn member_of(user: &User, groups: &[Group]) -> Vec<String> {
+    let mut result: Vec<String> = groups.iter().filter(|g| g.gid == user.gid).map(|g| g.name.clone()).collect();
+
+    result.extend(groups.iter()
+        .filter(|g| g.gid != user.gid && g.members.contains(&user.name))
+        .map(|g| g.name.clone()));
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::accounts::tests::fixture;
+
+    fn outcomes(result: Vec<(String, bool)>) -> Vec<bool> {
+        result.into_iter().map(|(_, success)| success).collect()
+    }
+
+    #[test]
+    fn user_lookups() {
+        let (accounts, _files) = fixture();
+        let check = |yaml: &str| {
+            let check: UserCheck = serde_yaml::from_str(yaml).unwrap();
+            outcomes(check.run_with(&accounts))
+        };
+
+        assert_eq!(check("{name: alice, uid: 1000, home: /home/alice, shell: /bin/bash}"), vec![true, true, true, true]);
+        assert_eq!(check("{name: alice, uid: 1001, shell: /bin/sh}"), vec![true, false, false]);
+        assert_eq!(check("{name: alice, group: alice}"), vec![true, true]);
+        assert_eq!(check("{name: alice, group: '1000'}"), vec![true, true]);
+        assert_eq!(check("{name: alice, group: sudo}"), vec![true, false]);
+        assert_eq!(check("{name: nobody, exists: false}"), vec![true]);
+        assert_eq!(check("{name: alice, exists: false}"), vec![false]);
+        assert_eq!(check("{name: nobody, uid: 1}"), vec![false]);
+    }
+
+    #[test]
+    fn user_membership() {
+        let (accounts, _files) = fixture();
+        let check: UserCheck = serde_yaml::from_str("{name: bob, groups: [bob, sudo, docker, alice]}").unwrap();
+
+        assert_eq!(outcomes(check.run_with(&accounts)), vec![true, true, true, true, false]);
+    }
+
+    #[test]
+    fn group_lookups() {
+        let (accounts, _files) = fixture();
+        let check = |yaml: &str| {
+            let check: GroupCheck = serde_yaml::from_str(yaml).unwrap();
+            outcomes(check.run_with(&accounts))
+        };
+
+        assert_eq!(check("{name: sudo, gid: 27}"), vec![true, true]);
+        assert_eq!(check("{name: sudo, gid: 28}"), vec![true, false]);
+        assert_eq!(check("{name: wheel, exists: false}"), vec![true]);
+        //alice is a member only because it is the primary group of the user alice.
+        assert_eq!(check("{name: alice, members: [alice, bob]}"), vec![true, true, false]);
+        assert_eq!(check("{name: docker, members: [alice, bob]}"), vec![true, true, true]);
+    }
+}
@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use anyhow::{bail, Result};
+
+/// Which packaging system's rules versions are compared with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scheme {
+    Dpkg,
+    Rpm,
+}
+
+/// Checks a version against constraints like `>= 1.2, < 2`. A version without an operator has to match exactly.
+/// If a constraint doesn't have a revision (the part after the last `-`), the installed revision is ignored.
+pub fn satisfies(scheme: Scheme, version: &str, constraints: &str) -> Result<bool> {
+    for constraint in constraints.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        let (op, wanted) = split_operator(constraint);
+
+        if wanted.is_empty() {
+            bail!("Missing version in constraint {constraint}");
+        }
+
+        let mut actual = parse(version);
+        let wanted = parse(wanted);
+
+        if wanted.revision.is_none() {
+            actual.revision = None;
+        }
+
+        let ordering = compare_parsed(scheme, &actual, &wanted);
+        let matches = match op {
+            "=" | "==" => ordering == Ordering::Equal,
+            "!=" => ordering != Ordering::Equal,
+            ">" | ">>" => ordering == Ordering::Greater,
+            ">=" => ordering != Ordering::Less,
+            "<" | "<<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            _ => bail!("Unknown operator {op} in constraint {constraint}"),
+        };
+
+        if !matches {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+struct Version<'a> {
+    epoch: u64,
+    upstream: &'a str,
+    revision: Option<&'a str>,
+}
+
+fn split_operator(constraint: &str) -> (&str, &str) {
+    let end = constraint.find(|c: char| !matches!(c, '<' | '>' | '=' | '!')).unwrap_or(constraint.len());
+    let op = if end == 0 { "=" } else { &constraint[..end] };
+
+    (op, constraint[end..].trim())
+}
+
+/// Splits `epoch:upstream-revision`.
+fn parse(version: &str) -> Version<'_> {
+    let version = version.trim();
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => (epoch.parse().unwrap_or(0), rest),
+        _ => (0, version),
+    };
+
+    match rest.rsplit_once('-') {
+        Some((upstream, revision)) => Version { epoch, upstream, revision: Some(revision) },
+        None => Version { epoch, upstream: rest, revision: None },
+    }
+}
+
+fn compare_parsed(scheme: Scheme, a: &Version, b: &Version) -> Ordering {
+    let segment = |x: &str, y: &str| match scheme {
+        Scheme::Dpkg => dpkg_compare(x, y),
+        Scheme::Rpm => rpm_compare(x, y),
+    };
+
+    a.epoch.cmp(&b.epoch)
+        .then_with(|| segment(a.upstream, b.upstream))
+        .then_with(|| match scheme {
+            //dpkg treats a missing revision as empty, rpm only compares releases if both have one.
+            Scheme::Dpkg => segment(a.revision.unwrap_or(""), b.revision.unwrap_or("")),
+            Scheme::Rpm => match (a.revision, b.revision) {
+                (Some(x), Some(y)) => segment(x, y),
+                _ => Ordering::Equal,
+            },
+        })
+}
+
+/// The version ordering dpkg uses, where `~` sorts before everything, even the end of the string.
+fn dpkg_compare(a: &str, b: &str) -> Ordering {
+    fn order(c: Option<u8>) -> i32 {
+        match c {
+            None => 0,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => c as i32,
+            Some(b'~') => -1,
+            Some(c) => c as i32 + 256,
+        }
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    let is_digit = |s: &[u8], idx: usize| s.get(idx).map(|c| c.is_ascii_digit()).unwrap_or(false);
+
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !is_digit(a, i)) || (j < b.len() && !is_digit(b, j)) {
+            let (ac, bc) = (order(a.get(i).copied()), order(b.get(j).copied()));
+
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+
+            i += 1;
+            j += 1;
+        }
+
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+
+        let mut first_diff = Ordering::Equal;
+
+        while is_digit(a, i) && is_digit(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+
+            i += 1;
+            j += 1;
+        }
+
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// The version ordering rpm uses, comparing runs of digits numerically and runs of letters alphabetically.
+fn rpm_compare(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    let separator = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+
+    loop {
+        while a.get(i).map(separator).unwrap_or(false) {
+            i += 1;
+        }
+
+        while b.get(j).map(separator).unwrap_or(false) {
+            j += 1;
+        }
+
+        let (ac, bc) = (a.get(i).copied(), b.get(j).copied());
+
+        if ac == Some(b'~') || bc == Some(b'~') {
+            if ac != Some(b'~') {
+                return Ordering::Greater;
+            }
+
+            if bc != Some(b'~') {
+                return Ordering::Less;
+            }
+
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        if ac == Some(b'^') || bc == Some(b'^') {
+            return match (ac, bc) {
+                (None, _) => Ordering::Less,
+                (_, None) => Ordering::Greater,
+                (Some(b'^'), Some(b'^')) => {
+                    i += 1;
+                    j += 1;
+                    continue;
+                }
+                (Some(b'^'), _) => Ordering::Less,
+                _ => Ordering::Greater,
+            };
+        }
+
+        let (Some(ac), Some(_)) = (ac, bc) else {
+            break;
+        };
+
+        let numeric = ac.is_ascii_digit();
+        let same_kind = |c: &u8| if numeric { c.is_ascii_digit() } else { c.is_ascii_alphabetic() };
+        let (start_a, start_b) = (i, j);
+
+        while a.get(i).map(same_kind).unwrap_or(false) {
+            i += 1;
+        }
+
+        while b.get(j).map(same_kind).unwrap_or(false) {
+            j += 1;
+        }
+
+        //A number is always newer than letters.
+        if start_b == j {
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let (mut seg_a, mut seg_b) = (&a[start_a..i], &b[start_b..j]);
+
+        if numeric {
+            while seg_a.first() == Some(&b'0') {
+                seg_a = &seg_a[1..];
+            }
+
+            while seg_b.first() == Some(&b'0') {
+                seg_b = &seg_b[1..];
+            }
+
+            match seg_a.len().cmp(&seg_b.len()) {
+                Ordering::Equal => {}
+                other => return other,
+            }
+        }
+
+        match seg_a.cmp(seg_b) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+    }
+
+    match (i < a.len(), j < b.len()) {
+        (false, false) => Ordering::Equal,
+        (true, _) => Ordering::Greater,
+        _ => Ordering::Less,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dpkg(a: &str, b: &str) -> Ordering {
+        compare_parsed(Scheme::Dpkg, &parse(a), &parse(b))
+    }
+
+    fn rpm(a: &str, b: &str) -> Ordering {
+        compare_parsed(Scheme::Rpm, &parse(a), &parse(b))
+    }
+
+    #[test]
+    fn tilde_sorts_before_the_release() {
+        assert_eq!(dpkg("1.0~rc1", "1.0"), Ordering::Less);
+        assert_eq!(dpkg("1.0~rc1", "1.0~rc2"), Ordering::Less);
+        assert_eq!(dpkg("1.0~~", "1.0~"), Ordering::Less);
+        assert_eq!(rpm("1.0~rc1", "1.0"), Ordering::Less);
+        assert_eq!(rpm("1.0~rc1", "1.0~rc2"), Ordering::Less);
+    }
+
+    #[test]
+    fn epoch_beats_upstream() {
+        assert_eq!(dpkg("1:0.1", "9.9"), Ordering::Greater);
+        assert_eq!(rpm("1:0.1-1", "9.9-1"), Ordering::Greater);
+        assert_eq!(dpkg("0:1.0", "1.0"), Ordering::Equal);
+        assert!(satisfies(Scheme::Dpkg, "2:1.0-1", ">= 1:5.0").unwrap());
+    }
+
+    #[test]
+    fn constraint_without_revision_ignores_installed_revision() {
+        assert!(satisfies(Scheme::Dpkg, "1.2.3-4ubuntu1", "= 1.2.3").unwrap());
+        assert!(satisfies(Scheme::Rpm, "1.2.3-4.el9", "1.2.3").unwrap());
+        assert!(!satisfies(Scheme::Dpkg, "1.2.3-4ubuntu1", "> 1.2.3").unwrap());
+        assert!(!satisfies(Scheme::Dpkg, "1.2.3-4ubuntu1", "= 1.2.3-5").unwrap());
+    }
+
+    #[test]
+    fn rpm_caret_sorts_after_the_release() {
+        assert_eq!(rpm("1.0^git1", "1.0"), Ordering::Greater);
+        assert_eq!(rpm("1.0^git1", "1.0.1"), Ordering::Less);
+        assert_eq!(rpm("1.0^git1", "1.0^git2"), Ordering::Less);
+        assert_eq!(rpm("1.0~rc1^git1", "1.0~rc1"), Ordering::Greater);
+        assert_eq!(rpm("1.0~rc1^git1", "1.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn letters_and_digits() {
+        assert_eq!(rpm("1.0a", "1.0.1"), Ordering::Less);
+        assert_eq!(rpm("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(rpm("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(rpm("1.010", "1.10"), Ordering::Equal);
+        assert_eq!(dpkg("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(dpkg("1.0a", "1.0+"), Ordering::Less);
+        assert_eq!(dpkg("1.0a", "1.0.1"), Ordering::Less);
+        assert_eq!(dpkg("1.10", "1.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn malformed_constraints_are_errors() {
+        assert!(satisfies(Scheme::Dpkg, "1.0", ">=").is_err());
+        assert!(satisfies(Scheme::Dpkg, "1.0", "=> 1.0").is_err());
+        assert!(satisfies(Scheme::Rpm, "1.5", ">= 1.0, < 2").unwrap());
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 use std::hash::Hash;
+use regex::{Captures, Regex};
+use crate::test_suite::EnvMode;
 
 pub trait HashMapExt<K: Hash + Eq + Clone, V: Clone> {
     fn append(&mut self, other: &HashMap<K, V>);
@@ -22,4 +24,96 @@ impl <K: Hash + Eq + Clone,V: Clone> HashMapExt<K,V> for HashMap<K,V> {
             self.append(other);
         }
     }
-}
\ No newline at end of file
+}
+
+/// The environment a step's command runs with: the variables it's allowed from weave-test's own environment with
+/// the step's on top. Variables that aren't valid unicode are left out.
+pub fn effective(mode: &EnvMode, passthrough: &[String], env: &HashMap<String, String>) -> HashMap<String, String> {
+    let allowed = |name: &str| passthrough.iter().any(|p| match p.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == p,
+    });
+
+    let mut result: HashMap<String, String> = std::env::vars_os()
+        .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+        .filter(|(k, _)| match mode {
+            EnvMode::Inherit => true,
+            EnvMode::Clean => false,
+            EnvMode::Allowlist => allowed(k),
+        })
+        .collect();
+
+    result.append(env);
+    result
+}
+
+/// Replaces `${NAME}` with the value of the variable, leaving any that aren't set as they are.
+pub fn interpolate(text: &str, env: &HashMap<String, String>) -> String {
+    let re = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+
+    re.replace_all(text, |caps: &Captures| {
+        env.get(&caps[1]).cloned().unwrap_or(caps[0].to_string())
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    /// Layered the way a step's variables are: suite, then step, then the data set row.
+    #[test]
+    fn later_sets_win() {
+        let suite = Some(vars(&[("HOST", "suite"), ("PORT", "80"), ("MODE", "suite")]));
+        let step = Some(vars(&[("HOST", "step"), ("MODE", "step")]));
+        let row = vars(&[("HOST", "row")]);
+
+        let mut env = HashMap::new();
+        env.try_append(&suite);
+        env.try_append(&step);
+        env.append(&row);
+
+        assert_eq!(env, vars(&[("HOST", "row"), ("PORT", "80"), ("MODE", "step")]));
+    }
+
+    #[test]
+    fn try_append_skips_missing_sets() {
+        let mut env = vars(&[("A", "1")]);
+        env.try_append(&None);
+        env.try_append(&Some(vars(&[("A", "2"), ("B", "3")])));
+
+        assert_eq!(env, vars(&[("A", "2"), ("B", "3")]));
+    }
+
+    #[test]
+    fn env_modes() {
+        std::env::set_var("WEAVE_TEST_ENV_KEPT", "kept");
+        std::env::set_var("WEAVE_TEST_ENV_PREFIX_ONE", "one");
+        std::env::set_var("WEAVE_TEST_ENV_DROPPED", "dropped");
+        let step = vars(&[("WEAVE_TEST_ENV_KEPT", "step"), ("STEP_ONLY", "yes")]);
+        let passthrough = vec!["WEAVE_TEST_ENV_PREFIX_*".to_string(), "WEAVE_TEST_ENV_KEPT".to_string()];
+
+        let inherit = effective(&EnvMode::Inherit, &[], &step);
+        assert_eq!(inherit.get("WEAVE_TEST_ENV_DROPPED").map(String::as_str), Some("dropped"));
+        assert_eq!(inherit.get("WEAVE_TEST_ENV_KEPT").map(String::as_str), Some("step"));
+
+        assert_eq!(effective(&EnvMode::Clean, &passthrough, &step), step);
+
+        let allowlist = effective(&EnvMode::Allowlist, &passthrough, &vars(&[]));
+        let mut names: Vec<&String> = allowlist.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["WEAVE_TEST_ENV_KEPT", "WEAVE_TEST_ENV_PREFIX_ONE"]);
+    }
+
+    #[test]
+    fn interpolation() {
+        let env = vars(&[("HOST", "web01"), ("PORT", "8080")]);
+
+        assert_eq!(interpolate("http://${HOST}:${PORT}/", &env), "http://web01:8080/");
+        assert_eq!(interpolate("${MISSING} and ${HOST}", &env), "${MISSING} and web01");
+        assert_eq!(interpolate("$HOST ${1X} ${HOST", &env), "$HOST ${1X} ${HOST");
+    }
+}
@@ -1,65 +1,576 @@
 use std::collections::HashMap;
-use std::ops::Sub;
-use std::process::{Stdio};
-use std::time::{Duration, SystemTime};
-use tokio::process::{Command, Child};
-use tokio_stream::Stream;
-use tokio_util::codec::{FramedRead, LinesCodec};
-use anyhow::Result;
-use tokio::time::timeout;
-use tokio_stream::StreamExt;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, watch};
+use anyhow::{bail, Result};
+use tokio::time::{sleep, timeout, timeout_at, Instant};
+use crate::limits::Limits;
+use crate::process_tree::{self, ProcessTree};
+use crate::run_as::Identity;
+use crate::shell::Invocation;
+use crate::temp::TempPath;
+use crate::test_suite::{EnvMode, ProtocolChannel};
+
+/// How often a channel file or FIFO is checked for new messages.
+const TAIL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// How long to give a command's exit to be noticed once its output has closed, before looking for leftovers.
+const EXIT_GRACE: Duration = Duration::from_millis(100);
+
+/// How long output is still read once the command has exited, when something it started holds the other end open.
+const DRAIN_GRACE: Duration = Duration::from_millis(100);
+
+pub enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+    Channel(String),
+}
+
+/// How a command exited, and the CPU time it used where the platform can tell us.
+#[derive(Debug, Clone, Copy)]
+pub struct Exit {
+    pub status: ExitStatus,
+    pub cpu_time: Option<Duration>,
+}
+
+impl Exit {
+    /// The signal that stopped the command, if one did.
+    pub fn signal(&self) -> Option<i32> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            self.status.signal()
+        }
+
+        #[cfg(not(unix))]
+        None
+    }
+}
+
+/// Everything a command wrote that wasn't a protocol message, and how it exited.
+#[derive(Default)]
+pub struct CommandOutput {
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    pub exit_code: Option<i32>,
+}
+
+/// Settings for how a command is started and watched.
+#[derive(Clone)]
+pub struct ExecOptions {
+    pub timeout: Option<Duration>,
+    pub channel: ProtocolChannel,
+    /// Folder the command runs in.
+    pub cwd: PathBuf,
+    /// Shell, or template like `python3 {file}`, commands are run with.
+    pub shell: Option<String>,
+    pub limits: Option<Limits>,
+    pub env_mode: EnvMode,
+    pub env_passthrough: Vec<String>,
+    /// User and groups the command runs as, already checked we're able to switch to.
+    pub run_as: Option<Identity>,
+}
 
 pub struct RunningCommand {
-    time_left: Option<Duration>,
-    _process: Child,
-    stream: Box<dyn Stream<Item = String> + Unpin>,
+    /// When the command's timeout runs out.
+    deadline: Option<Instant>,
+    /// The command and everything it started.
+    tree: Arc<ProcessTree>,
+    receiver: mpsc::Receiver<OutputLine>,
+    _kill: oneshot::Sender<()>,
+    exit: watch::Receiver<Option<Exit>>,
+    _channel_path: Option<TempPath>,
+    _script: Option<TempPath>,
+}
+
+/// Where the protocol channel of a child can be found and how we read it.
+enum ChannelSource {
+    None,
+    Tail(TempPath),
+    #[cfg(unix)]
+    Pipe(File, std::os::fd::OwnedFd),
 }
 
 impl RunningCommand {
-    pub fn new(command: &String, environment: &HashMap<String, String>, timeout: Option<Duration>) -> Result<Box<RunningCommand>> {
-        let mut process = if cfg!(target_os = "windows") {
-            Command::new("cmd")
-                .envs(environment)
-                .args(["/C", command])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null())
-                .stdin(Stdio::null())
-                .kill_on_drop(true)
-                .spawn()?
-        } else {
-            Command::new("sh")
-                .envs(environment)
-                .args(["-c", command])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .stdin(Stdio::null())
-                .kill_on_drop(true)
-                .spawn()?
+    pub fn new(invocation: Invocation, environment: &HashMap<String, String>, stdin: Option<Vec<u8>>, options: &ExecOptions) -> Result<Box<RunningCommand>> {
+        let identity = &options.run_as;
+
+        let mut process = Command::new(&invocation.program);
+
+        process
+            .args(&invocation.args)
+            .env_clear()
+            .envs(environment)
+            .current_dir(&options.cwd)
+            .stdout(Stdio::piped())
+            .stderr(if cfg!(target_os = "windows") { Stdio::null() } else { Stdio::piped() })
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .kill_on_drop(true);
+
+        if process_tree::is_cancelled() {
+            bail!("The run was cancelled");
+        }
+
+        //Its own process group, so everything the command starts can be stopped along with it.
+        #[cfg(unix)]
+        unsafe {
+            process.pre_exec(|| match libc::setpgid(0, 0) {
+                0 => Ok(()),
+                _ => Err(std::io::Error::last_os_error()),
+            });
+        }
+
+        if let Some(limits) = options.limits {
+            set_limits(&mut process, limits)?;
+        }
+
+        if let Some(identity) = identity {
+            if let Some(script) = &invocation.script {
+                identity.give(script.path())?;
+            }
+
+            set_identity(&mut process, identity.clone());
+        }
+
+        let source = open_channel(&options.channel)?;
+
+        if let (Some(identity), ChannelSource::Tail(path)) = (identity, &source) {
+            identity.give(path.path())?;
+        }
+
+        match &source {
+            ChannelSource::None => {}
+            ChannelSource::Tail(path) => {
+                process.env("WEAVE_TEST_CHANNEL", path.path());
+            }
+            #[cfg(unix)]
+            ChannelSource::Pipe(_, writer) => {
+                use std::os::fd::AsRawFd;
+                let fd = writer.as_raw_fd();
+                process.env("WEAVE_TEST_CHANNEL", format!("/dev/fd/{fd}"));
+                process.env("WEAVE_TEST_CHANNEL_FD", fd.to_string());
+            }
+        }
+
+        let mut child = match process.spawn() {
+            Ok(child) => child,
+            Err(e) => bail!("{}: {e}", invocation.program),
+        };
+
+        let tree = Arc::new(ProcessTree::new(&child)?);
+        process_tree::register(&tree)?;
+
+        let (sender, receiver) = mpsc::channel(100);
+        let (kill, kill_signal) = oneshot::channel::<()>();
+        let (exited, exit_signal) = watch::channel(None);
+
+        if let Some(stdout) = child.stdout.take() {
+            spawn_line_reader(stdout, sender.clone(), OutputLine::Stdout, exit_signal.clone());
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            spawn_line_reader(stderr, sender.clone(), OutputLine::Stderr, exit_signal.clone());
+        }
+
+        //Input is written on its own task so a child that fills its output before reading everything doesn't block us.
+        if let (Some(mut input), Some(data)) = (child.stdin.take(), stdin) {
+            tokio::spawn(async move {
+                //The child can exit without reading it all, which is fine.
+                let _ = input.write_all(&data).await;
+            });
+        }
+
+        let channel_path = match source {
+            ChannelSource::None => None,
+            ChannelSource::Tail(path) => {
+                let file = open_for_tail(&path)?;
+                spawn_tail_reader(file, sender.clone(), exit_signal.clone());
+                Some(path)
+            }
+            #[cfg(unix)]
+            ChannelSource::Pipe(reader, writer) => {
+                //Our copy of the write end has to be closed so we see EOF once the child is done with it.
+                drop(writer);
+                let reader = tokio::net::unix::pipe::Receiver::from_file(reader)?;
+                spawn_line_reader(reader, sender.clone(), OutputLine::Channel, exit_signal.clone());
+                None
+            }
         };
 
-        let stdout = FramedRead::new(process.stdout.take().unwrap(), LinesCodec::new())
-            .map(|data| data.expect("fail on out!"));
+        drop(sender);
+
+        let waiter_tree = tree.clone();
 
-        let stderr = FramedRead::new(process.stderr.take().unwrap(), LinesCodec::new())
-            .map(|data| data.expect("fail on err!"));
+        tokio::spawn(async move {
+            if let Some(exit) = wait_for_exit(child, kill_signal, waiter_tree).await {
+                let _ = exited.send(Some(exit));
+            }
+        });
 
-        let stream: Box<dyn Stream<Item = String> + Unpin> = Box::new(stdout.chain(stderr));
-        
         Ok(Box::new(RunningCommand{
-            time_left: timeout,
-            _process: process,
-            stream,
+            deadline: options.timeout.and_then(|t| Instant::now().checked_add(t)),
+            tree,
+            receiver,
+            _kill: kill,
+            exit: exit_signal,
+            _channel_path: channel_path,
+            _script: invocation.script,
         }))
     }
 
-    pub async fn next_line(&mut self) -> Result<Option<String>> {
-        if let Some(time_left) = self.time_left {
-            let start_time = SystemTime::now();
-            let result = timeout(time_left, self.stream.next()).await?;
-            self.time_left = Some(time_left.sub(SystemTime::now().duration_since(start_time)?));
-            Ok(result)
-        } else{
-            Ok(self.stream.next().await)
+    /// Waits for the next line of output. Fails once the timeout is used up, even if there is output waiting.
+    pub async fn next_line(&mut self) -> Result<Option<OutputLine>> {
+        match self.deadline {
+            Some(deadline) => {
+                if deadline.saturating_duration_since(Instant::now()).is_zero() {
+                    bail!("Command timed out");
+                }
+
+                Ok(timeout_at(deadline, self.receiver.recv()).await?)
+            }
+            None => Ok(self.receiver.recv().await),
+        }
+    }
+
+    /// Waits for the command to exit and returns its exit code. This is `None` if it was stopped by a signal.
+    pub async fn exit_code(&mut self) -> Result<Option<i32>> {
+        Ok(self.exit().await?.status.code())
+    }
+
+    /// Waits for the command to exit.
+    pub async fn exit(&mut self) -> Result<Exit> {
+        let wait = self.exit.wait_for(|s| s.is_some());
+
+        let exit = match self.deadline {
+            Some(deadline) => *timeout_at(deadline, wait).await??,
+            None => *wait.await?,
+        };
+
+        match exit {
+            Some(exit) => Ok(exit),
+            None => bail!("Command exited without a status"),
         }
     }
-}
\ No newline at end of file
+
+    /// Stops the command and everything it started, asking nicely with SIGTERM before killing them. Returns a
+    /// line for each process that had to be killed or is still running afterwards.
+    pub async fn stop(&mut self) -> Vec<String> {
+        self.tree.stop().await
+    }
+
+    /// Processes the command started that are still running now it has finished, like a server started in the
+    /// background. They're left running, apart from on Windows where closing the job ends them.
+    pub async fn leftovers(&mut self) -> Vec<String> {
+        if timeout(EXIT_GRACE, self.exit.wait_for(|s| s.is_some())).await.is_err() {
+            return vec![];
+        }
+
+        self.tree.members()
+    }
+}
+
+impl Drop for RunningCommand {
+    fn drop(&mut self) {
+        process_tree::unregister(&self.tree);
+    }
+}
+
+/// Waits for the child to exit, or kills everything it started if the command is dropped first. On Linux the
+/// CPU time it used is read before it's reaped, which is the only point it can be.
+#[cfg(target_os = "linux")]
+async fn wait_for_exit(mut child: Child, mut kill_signal: oneshot::Receiver<()>, tree: Arc<ProcessTree>) -> Option<Exit> {
+    let pid = child.id()?;
+    let mut usage = tokio::task::spawn_blocking(move || cpu_time_at_exit(pid));
+
+    let cpu_time = tokio::select! {
+        cpu_time = &mut usage => cpu_time.ok().flatten(),
+        //Dropped before the command finished, so nothing it started is wanted any more.
+        _ = &mut kill_signal => {
+            tree.kill();
+            usage.await.ok().flatten()
+        }
+    };
+
+    let status = child.wait().await.ok()?;
+    Some(Exit { status, cpu_time })
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn wait_for_exit(mut child: Child, kill_signal: oneshot::Receiver<()>, tree: Arc<ProcessTree>) -> Option<Exit> {
+    let status = tokio::select! {
+        status = child.wait() => status,
+        //Dropped before the command finished, so nothing it started is wanted any more.
+        _ = kill_signal => {
+            tree.kill();
+            let _ = child.kill().await;
+            child.wait().await
+        }
+    };
+
+    status.ok().map(|status| Exit { status, cpu_time: None })
+}
+
+/// Blocks until the child has exited, without reaping it, and returns the CPU time it and the children it waited
+/// for used. The waitid system call takes an rusage the libc wrapper doesn't expose.
+#[cfg(target_os = "linux")]
+fn cpu_time_at_exit(pid: u32) -> Option<Duration> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+        let result = unsafe {
+            libc::syscall(libc::SYS_waitid, libc::P_PID, pid as libc::c_long, &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT, &mut usage as *mut libc::rusage)
+        };
+
+        if result == 0 {
+            let time = |t: libc::timeval| Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64);
+            return Some(time(usage.ru_utime) + time(usage.ru_stime));
+        }
+
+        if std::io::Error::last_os_error().kind() != ErrorKind::Interrupted {
+            return None;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_limits(process: &mut Command, limits: Limits) -> Result<()> {
+    unsafe {
+        process.pre_exec(move || limits.apply());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_limits(_process: &mut Command, _limits: Limits) -> Result<()> {
+    bail!("Resource limits are not supported on this platform!");
+}
+
+#[cfg(unix)]
+fn set_identity(process: &mut Command, identity: Identity) {
+    unsafe {
+        process.pre_exec(move || identity.apply());
+    }
+}
+
+//check_privilege has already failed by now.
+#[cfg(not(unix))]
+fn set_identity(_process: &mut Command, _identity: Identity) {}
+
+/// Sends each line read until the other end is closed. Output that isn't valid UTF-8 is read lossily. Anything the
+/// command started inherits the other end too, so once the command has exited we only read what's left.
+fn spawn_line_reader<R, F>(reader: R, sender: mpsc::Sender<OutputLine>, wrap: F, mut exited: watch::Receiver<Option<Exit>>)
+    where R: AsyncRead + Unpin + Send + 'static, F: Fn(String) -> OutputLine + Send + 'static {
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        let mut line: Vec<u8> = vec![];
+        let mut finished = false;
+
+        loop {
+            let read = if finished {
+                //Nothing more within the grace period means whatever is left writing isn't the command.
+                timeout(DRAIN_GRACE, reader.read_until(b'\n', &mut line)).await.unwrap_or(Ok(0))
+            } else {
+                tokio::select! {
+                    read = reader.read_until(b'\n', &mut line) => read,
+                    _ = exited.wait_for(|s| s.is_some()) => {
+                        finished = true;
+                        continue;
+                    }
+                }
+            };
+
+            match read {
+                Ok(count) if count > 0 && line.ends_with(b"\n") => {
+                    if sender.send(wrap(to_line(&line))).await.is_err() {
+                        return;
+                    }
+
+                    line.clear();
+                }
+                Ok(count) if count > 0 => {}
+                _ => break,
+            }
+        }
+
+        if !line.is_empty() {
+            let _ = sender.send(wrap(to_line(&line))).await;
+        }
+    });
+}
+
+/// A line of output without its line ending.
+fn to_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_end_matches(['\r', '\n']).to_string()
+}
+
+/// Follows a file or FIFO the child writes messages to until the child has exited and everything has been read.
+fn spawn_tail_reader(file: File, sender: mpsc::Sender<OutputLine>, mut exited: watch::Receiver<Option<Exit>>) {
+    tokio::spawn(async move {
+        //Reads on a tokio file happen on the blocking pool, so a slow disk doesn't hold up other steps.
+        let mut file = tokio::fs::File::from_std(file);
+        let mut pending: Vec<u8> = vec![];
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            let finished = exited.borrow().is_some();
+
+            match file.read(&mut buffer).await {
+                Ok(count) if count > 0 => {
+                    pending.extend_from_slice(&buffer[..count]);
+
+                    while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+                        let line: Vec<u8> = pending.drain(..=pos).collect();
+
+                        if sender.send(OutputLine::Channel(to_line(&line))).await.is_err() {
+                            return;
+                        }
+                    }
+
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+
+            if finished {
+                break;
+            }
+
+            tokio::select! {
+                _ = exited.changed() => {}
+                _ = sleep(TAIL_INTERVAL) => {}
+            }
+        }
+
+        if !pending.is_empty() {
+            let _ = sender.send(OutputLine::Channel(to_line(&pending))).await;
+        }
+    });
+}
+
+fn open_channel(channel: &ProtocolChannel) -> Result<ChannelSource> {
+    match channel {
+        ProtocolChannel::Stdout => Ok(ChannelSource::None),
+        ProtocolChannel::File => {
+            let (path, _) = TempPath::create("channel", ".log")?;
+            Ok(ChannelSource::Tail(path))
+        }
+        ProtocolChannel::Fifo => open_fifo(),
+        ProtocolChannel::Pipe => open_pipe(),
+    }
+}
+
+#[cfg(unix)]
+fn open_fifo() -> Result<ChannelSource> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = TempPath::new("channel", ".fifo");
+    let c_path = CString::new(path.path().as_os_str().as_bytes())?;
+
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        bail!("Unable to create protocol channel FIFO: {}", std::io::Error::last_os_error());
+    }
+
+    Ok(ChannelSource::Tail(path))
+}
+
+#[cfg(not(unix))]
+fn open_fifo() -> Result<ChannelSource> {
+    bail!("The fifo protocol channel is not supported on this platform!");
+}
+
+#[cfg(unix)]
+fn open_pipe() -> Result<ChannelSource> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        bail!("Unable to create protocol channel pipe: {}", std::io::Error::last_os_error());
+    }
+
+    //Only the write end should be inherited by the child.
+    unsafe { libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC) };
+
+    let reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { OwnedFd::from_raw_fd(fds[1]) };
+
+    Ok(ChannelSource::Pipe(reader, writer))
+}
+
+#[cfg(not(unix))]
+fn open_pipe() -> Result<ChannelSource> {
+    bail!("The pipe protocol channel is not supported on this platform!");
+}
+
+#[cfg(unix)]
+fn open_for_tail(path: &TempPath) -> Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    //Non blocking so opening a FIFO doesn't wait for the child to open the other end.
+    Ok(std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path.path())?)
+}
+
+#[cfg(not(unix))]
+fn open_for_tail(path: &TempPath) -> Result<File> {
+    Ok(File::open(path.path())?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn options(timeout: Duration) -> ExecOptions {
+        ExecOptions {
+            timeout: Some(timeout),
+            channel: ProtocolChannel::Stdout,
+            cwd: PathBuf::from("."),
+            shell: None,
+            limits: None,
+            env_mode: EnvMode::Inherit,
+            env_passthrough: vec![],
+            run_as: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn tiny_timeout_with_lots_of_output_times_out() {
+        for _ in 0..20 {
+            let invocation = Invocation::shell(Some("sh"), "yes | head -c 2000000").unwrap();
+            let mut exec = RunningCommand::new(invocation, &HashMap::new(), None, &options(Duration::from_millis(2))).unwrap();
+
+            let timed_out = loop {
+                match exec.next_line().await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => break false,
+                    Err(_) => break true,
+                }
+            };
+
+            assert!(timed_out);
+            exec.stop().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn zero_timeout_is_a_timeout() {
+        let invocation = Invocation::shell(Some("sh"), "echo hi").unwrap();
+        let mut exec = RunningCommand::new(invocation, &HashMap::new(), None, &options(Duration::ZERO)).unwrap();
+
+        assert!(exec.next_line().await.is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use regex::Regex;
+use similar::{ChangeTag, TextDiff};
+use serde_json::Value;
+use crate::exec::CommandOutput;
+use crate::json_path;
+use crate::test_suite::{Expectations, JsonExpectation};
+
+/// Most lines of output shown when an expectation fails.
+const MAX_SHOWN_LINES: usize = 20;
+
+/// Checks a finished command against the expectations of its step. Returns the message and outcome of each check.
+pub fn evaluate(expect: &Expectations, output: &CommandOutput) -> Vec<(String, bool)> {
+    let mut result = vec![];
+
+    if let Some(expected) = expect.exit_code {
+        result.push(match output.exit_code {
+            Some(code) if code == expected => (format!("exit code is {expected}"), true),
+            Some(code) => (format!("exit code is {expected}\n  expected: {expected}\n  actual:   {code}"), false),
+            None => (format!("exit code is {expected}\n  the command was terminated by a signal"), false),
+        });
+    }
+
+    if let Some(items) = &expect.stdout_contains {
+        for expected in items.items() {
+            let message = format!("stdout contains \"{expected}\"");
+
+            if output.stdout.join("\n").contains(expected.as_str()) {
+                result.push((message, true));
+            } else {
+                result.push((format!("{message}\n{}", closest_diff(expected, &output.stdout)), false));
+            }
+        }
+    }
+
+    if let Some(items) = &expect.stdout_not_contains {
+        for unexpected in items.items() {
+            let message = format!("stdout does not contain \"{unexpected}\"");
+            let found: Vec<String> = output.stdout.iter().enumerate()
+                .filter(|(_, l)| l.contains(unexpected.as_str()))
+                .map(|(i, l)| format!("  line {}: {l}", i + 1))
+                .take(MAX_SHOWN_LINES)
+                .collect();
+
+            if found.is_empty() {
+                result.push((message, true));
+            } else {
+                result.push((format!("{message}\n{}", found.join("\n")), false));
+            }
+        }
+    }
+
+    if let Some(items) = &expect.stdout_matches {
+        for pattern in items.items() {
+            let message = format!("stdout matches /{pattern}/");
+
+            match Regex::new(pattern) {
+                Ok(re) if re.is_match(&output.stdout.join("\n")) => result.push((message, true)),
+                Ok(_) => result.push((format!("{message}\n  stdout was:\n{}", show_lines(&output.stdout)), false)),
+                Err(e) => result.push((format!("{message}\n  invalid regular expression: {e}"), false)),
+            }
+        }
+    }
+
+    if let Some(true) = expect.stderr_empty {
+        if output.stderr.is_empty() {
+            result.push(("stderr is empty".to_string(), true));
+        } else {
+            result.push((format!("stderr is empty\n  stderr was:\n{}", show_lines(&output.stderr)), false));
+        }
+    }
+
+    if let Some(items) = &expect.json {
+        result.append(&mut evaluate_json(items, &output.stdout.join("\n")));
+    }
+
+    result
+}
+
+/// Parses the text as JSON and checks the values at each path.
+pub fn evaluate_json(items: &[JsonExpectation], text: &str) -> Vec<(String, bool)> {
+    let document: Value = match serde_json::from_str(text) {
+        Ok(doc) => doc,
+        Err(e) => return vec![(format!("output is valid JSON\n  {e}"), false)],
+    };
+
+    items.iter().map(|item| check_json(item, &document)).collect()
+}
+
+fn check_json(item: &JsonExpectation, document: &Value) -> (String, bool) {
+    let found = match json_path::query(document, &item.path) {
+        Ok(found) => found,
+        Err(e) => return (format!("json {}\n  {e}", item.path), false),
+    };
+
+    let mut checks: Vec<(String, bool)> = vec![];
+
+    if let Some(exists) = item.exists {
+        let name = if exists { "exists" } else { "does not exist" };
+        checks.push((name.to_string(), found.is_some() == exists));
+    }
+
+    if let Some(expected) = &item.equals {
+        checks.push((format!("equals {expected}"), found.map(|v| json_equal(v, expected)).unwrap_or(false)));
+    }
+
+    if let Some(pattern) = &item.matches {
+        match Regex::new(pattern) {
+            Ok(re) => checks.push((format!("matches /{pattern}/"), found.map(|v| re.is_match(&plain_text(v))).unwrap_or(false))),
+            Err(e) => checks.push((format!("matches /{pattern}/ (invalid regular expression: {e})"), false)),
+        }
+    }
+
+    if let Some(expected) = &item.value_type {
+        checks.push((format!("is of type {expected}"), found.map(|v| json_path::type_name(v) == expected.to_lowercase()).unwrap_or(false)));
+    }
+
+    if let Some(expected) = item.length {
+        let length = found.and_then(|v| match v {
+            Value::Array(items) => Some(items.len()),
+            Value::Object(map) => Some(map.len()),
+            Value::String(text) => Some(text.chars().count()),
+            _ => None,
+        });
+
+        checks.push((format!("has length {expected}"), length == Some(expected)));
+    }
+
+    if checks.is_empty() {
+        checks.push(("exists".to_string(), found.is_some()));
+    }
+
+    let description = checks.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>().join(", ");
+    let message = format!("json {} {description}", item.path);
+
+    if checks.iter().all(|(_, success)| *success) {
+        return (message, true);
+    }
+
+    let actual = match found {
+        Some(value) => format!("  actual: {value}"),
+        None => "  actual: nothing at this path".to_string(),
+    };
+
+    (format!("{message}\n{actual}"), false)
+}
+
+/// Numbers are compared by value so `1` and `1.0` are the same.
+fn json_equal(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => actual == expected,
+    }
+}
+
+fn plain_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Shows how the expected text differs from the line of output that is most like it.
+fn closest_diff(expected: &str, lines: &[String]) -> String {
+    let closest = lines.iter()
+        .max_by(|a, b| {
+            let a = TextDiff::from_chars(expected, a.as_str()).ratio();
+            let b = TextDiff::from_chars(expected, b.as_str()).ratio();
+            a.total_cmp(&b)
+        });
+
+    let Some(closest) = closest else {
+        return "  stdout was empty".to_string();
+    };
+
+    let mut text = String::from("  closest line of stdout:\n");
+    let diff = TextDiff::from_words(expected, closest.as_str());
+
+    for (tag, sign) in [(ChangeTag::Delete, "- "), (ChangeTag::Insert, "+ ")] {
+        text.push_str("  ");
+        text.push_str(sign);
+
+        for change in diff.iter_all_changes() {
+            if change.tag() == tag {
+                text.push_str(&format!("[{}]", change.value()));
+            } else if change.tag() == ChangeTag::Equal {
+                text.push_str(change.value());
+            }
+        }
+
+        text.push('\n');
+    }
+
+    text.trim_end().to_string()
+}
+
+fn show_lines(lines: &[String]) -> String {
+    if lines.is_empty() {
+        return "    <empty>".to_string();
+    }
+
+    let mut shown: Vec<String> = lines.iter().take(MAX_SHOWN_LINES).map(|l| format!("    {l}")).collect();
+
+    if lines.len() > MAX_SHOWN_LINES {
+        shown.push(format!("    ... {} more lines", lines.len() - MAX_SHOWN_LINES));
+    }
+
+    shown.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(stdout: &str, stderr: &str, exit_code: Option<i32>) -> CommandOutput {
+        CommandOutput {
+            stdout: stdout.lines().map(String::from).collect(),
+            stderr: stderr.lines().map(String::from).collect(),
+            exit_code,
+        }
+    }
+
+    /// The outcome of each check for an `expect` block written in YAML.
+    fn outcomes(expect: &str, output: &CommandOutput) -> Vec<bool> {
+        let expect: Expectations = serde_yaml::from_str(expect).unwrap();
+        evaluate(&expect, output).into_iter().map(|(_, success)| success).collect()
+    }
+
+    #[test]
+    fn each_expectation() {
+        let ok = output("hello world\n{\"id\": 1, \"tags\": [\"a\", \"b\"]}", "", Some(0));
+        let failed = output("", "something broke", Some(2));
+        let killed = output("", "", None);
+
+        let table: Vec<(&str, &CommandOutput, Vec<bool>)> = vec![
+            ("exit_code: 0", &ok, vec![true]),
+            ("exit_code: 0", &failed, vec![false]),
+            ("exit_code: 0", &killed, vec![false]),
+            ("stdout_contains: world", &ok, vec![true]),
+            ("stdout_contains: [hello, goodbye]", &ok, vec![true, false]),
+            ("stdout_contains: hello", &failed, vec![false]),
+            ("stdout_not_contains: goodbye", &ok, vec![true]),
+            ("stdout_not_contains: [hello, goodbye]", &ok, vec![false, true]),
+            ("stdout_matches: '^hello \\w+'", &ok, vec![true]),
+            ("stdout_matches: '^goodbye'", &ok, vec![false]),
+            ("stdout_matches: '('", &ok, vec![false]),
+            ("stderr_empty: true", &ok, vec![true]),
+            ("stderr_empty: true", &failed, vec![false]),
+            ("stderr_empty: false", &failed, vec![]),
+            ("{exit_code: 2, stderr_empty: true}", &failed, vec![true, false]),
+        ];
+
+        for (expect, output, expected) in table {
+            assert_eq!(outcomes(expect, output), expected, "{expect}");
+        }
+    }
+
+    #[test]
+    fn each_json_expectation() {
+        let text = r#"{"id": 1, "name": "web01", "tags": ["a", "b"], "port": null}"#;
+
+        let table: Vec<(&str, bool)> = vec![
+            ("path: $.id", true),
+            ("{path: $.missing, exists: false}", true),
+            ("{path: $.missing, exists: true}", false),
+            ("{path: $.id, equals: 1.0}", true),
+            ("{path: $.id, equals: 2}", false),
+            ("{path: $.name, matches: '^web\\d+$'}", true),
+            ("{path: $.name, matches: '^db'}", false),
+            ("{path: $.tags, type: array}", true),
+            ("{path: $.port, type: string}", false),
+            ("{path: $.tags, length: 2}", true),
+            ("{path: $.name, length: 3}", false),
+        ];
+
+        for (item, expected) in table {
+            let item: JsonExpectation = serde_yaml::from_str(item).unwrap();
+            let result = evaluate_json(&[item], text);
+            assert_eq!(result[0].1, expected, "{}", result[0].0);
+        }
+    }
+
+    #[test]
+    fn output_that_isnt_json() {
+        let item: JsonExpectation = serde_yaml::from_str("path: $.id").unwrap();
+
+        assert!(!evaluate_json(&[item], "not json")[0].1);
+    }
+
+    #[test]
+    fn failures_show_what_was_found() {
+        let expect: Expectations = serde_yaml::from_str("{exit_code: 0, stdout_contains: hello wrld}").unwrap();
+        let result = evaluate(&expect, &output("hello world", "", Some(3)));
+
+        assert!(result[0].0.contains("actual:   3"), "{}", result[0].0);
+        assert!(result[1].0.contains("closest line of stdout"), "{}", result[1].0);
+    }
+}
@@ -0,0 +1,37 @@
+@rem Helpers added by weave-test. Messages go to the protocol channel if there is one, otherwise stdout.
+@rem Use them with call, like call :assert_pass "it works". Messages can't contain characters cmd treats specially, like ampersands.
+@goto :weave_test_script
+
+:weave_message
+@if not defined WEAVE_TEST_CHANNEL goto :weave_message_stdout
+@>> "%WEAVE_TEST_CHANNEL%" echo(%~1
+@goto :eof
+:weave_message_stdout
+@echo(%~1
+@goto :eof
+
+:assert_pass
+@call :weave_message "WEAVE-TEST:PASS: %~1"
+@goto :eof
+
+:assert_fail
+@call :weave_message "WEAVE-TEST:FAIL: %~1"
+@goto :eof
+
+:test_print
+@call :weave_message "WEAVE-TEST:PRINT: %~1"
+@goto :eof
+
+:test_group
+@call :weave_message "WEAVE-TEST:GROUP:%~1"
+@goto :eof
+
+:test_end_group
+@call :weave_message "WEAVE-TEST:ENDGROUP"
+@goto :eof
+
+:test_attach
+@call :weave_message "WEAVE-TEST:ATTACH:%~1:%~2"
+@goto :eof
+
+:weave_test_script
@@ -0,0 +1,16 @@
+# Helpers added by weave-test. Messages go to the protocol channel if there is one, otherwise stdout.
+function weave_message([string]$message) {
+    if ($env:WEAVE_TEST_CHANNEL) {
+        Add-Content -Path $env:WEAVE_TEST_CHANNEL -Value $message
+    } else {
+        Write-Output $message
+    }
+}
+
+function assert_pass([string]$message) { weave_message "WEAVE-TEST:PASS: $message" }
+function assert_fail([string]$message) { weave_message "WEAVE-TEST:FAIL: $message" }
+function test_print([string]$message) { weave_message "WEAVE-TEST:PRINT: $message" }
+function test_group([string]$name) { weave_message "WEAVE-TEST:GROUP:$name" }
+function test_end_group { weave_message "WEAVE-TEST:ENDGROUP" }
+function test_attach([string]$path, [string]$label = "") { weave_message "WEAVE-TEST:ATTACH:${path}:$label" }
+
@@ -0,0 +1,38 @@
+# Helpers added by weave-test. Messages go to the protocol channel if there is one, otherwise stdout.
+import os as _weave_os
+
+
+def weave_message(message):
+    channel = _weave_os.environ.get("WEAVE_TEST_CHANNEL")
+
+    if channel:
+        with open(channel, "a") as f:
+            f.write(message + "\n")
+    else:
+        print(message, flush=True)
+
+
+def assert_pass(message):
+    weave_message("WEAVE-TEST:PASS: " + message)
+
+
+def assert_fail(message):
+    weave_message("WEAVE-TEST:FAIL: " + message)
+
+
+def test_print(message):
+    weave_message("WEAVE-TEST:PRINT: " + message)
+
+
+def test_group(name):
+    weave_message("WEAVE-TEST:GROUP:" + name)
+
+
+def test_end_group():
+    weave_message("WEAVE-TEST:ENDGROUP")
+
+
+def test_attach(path, label=""):
+    weave_message("WEAVE-TEST:ATTACH:" + path + ":" + label)
+
+
@@ -0,0 +1,33 @@
+# Helpers added by weave-test. Messages go to the protocol channel if there is one, otherwise stdout.
+weave_message() {
+  if [ -n "$WEAVE_TEST_CHANNEL" ]; then
+    echo "$1" >> "$WEAVE_TEST_CHANNEL"
+  else
+    echo "$1"
+  fi
+}
+
+assert_pass() {
+  weave_message "WEAVE-TEST:PASS: $1"
+}
+
+assert_fail() {
+  weave_message "WEAVE-TEST:FAIL: $1"
+}
+
+test_print() {
+  weave_message "WEAVE-TEST:PRINT: $1"
+}
+
+test_group() {
+  weave_message "WEAVE-TEST:GROUP:$1"
+}
+
+test_end_group() {
+  weave_message "WEAVE-TEST:ENDGROUP"
+}
+
+test_attach() {
+  weave_message "WEAVE-TEST:ATTACH:$1:$2"
+}
+
@@ -0,0 +1,193 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    /// A negative index, counting back from the end of the array so `[-1]` is the last item.
+    FromEnd(usize),
+}
+
+/// Looks up a value with a simple path like `$.items[0].name`, `$.items[-1]` or `metadata["app.kubernetes.io/name"]`.
+/// Returns `None` if nothing is at the path.
+pub fn query<'a>(value: &'a Value, path: &str) -> Result<Option<&'a Value>> {
+    let mut current = value;
+
+    for segment in parse(path)? {
+        let next = match (&segment, current) {
+            (Segment::Key(key), Value::Object(map)) => map.get(key),
+            (Segment::Index(idx), Value::Array(items)) => items.get(*idx),
+            (Segment::FromEnd(back), Value::Array(items)) => items.len().checked_sub(*back).and_then(|idx| items.get(idx)),
+            _ => None,
+        };
+
+        match next {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+/// Name of a JSON value's type as used by `type:` checks.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let chars: Vec<char> = path.chars().collect();
+    let mut segments = vec![];
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        match chars[pos] {
+            '.' => {
+                pos += 1;
+            }
+            '[' => {
+                pos += 1;
+
+                while pos < chars.len() && chars[pos].is_whitespace() {
+                    pos += 1;
+                }
+
+                //Quoted keys can have anything in them, even `]`, so they end at the closing quote.
+                let quote = chars.get(pos).copied().filter(|c| *c == '"' || *c == '\'');
+                let inner_start = pos + quote.map_or(0, |_| 1);
+
+                let inner_end = match quote {
+                    Some(quote) => match chars[inner_start..].iter().position(|c| *c == quote) {
+                        Some(len) => inner_start + len,
+                        None => bail!("Missing closing {quote} in JSON path {path}"),
+                    },
+                    None => inner_start,
+                };
+
+                let Some(close) = chars[inner_end..].iter().position(|c| *c == ']').map(|len| inner_end + len) else {
+                    bail!("Missing ] in JSON path {path}");
+                };
+
+                if quote.is_some() {
+                    if chars[inner_end + 1..close].iter().any(|c| !c.is_whitespace()) {
+                        bail!("Unexpected text after quoted key in JSON path {path}");
+                    }
+
+                    segments.push(Segment::Key(chars[inner_start..inner_end].iter().collect()));
+                } else {
+                    let inner: String = chars[inner_start..close].iter().collect();
+                    let inner = inner.trim();
+
+                    let segment = match inner.strip_prefix('-') {
+                        Some(back) => back.parse().ok().filter(|b| *b > 0).map(Segment::FromEnd),
+                        None => inner.parse().ok().map(Segment::Index),
+                    };
+
+                    match segment {
+                        Some(segment) => segments.push(segment),
+                        None => bail!("Invalid index [{inner}] in JSON path {path}"),
+                    }
+                }
+
+                pos = close + 1;
+            }
+            _ => {
+                let start = pos;
+
+                while pos < chars.len() && chars[pos] != '.' && chars[pos] != '[' {
+                    pos += 1;
+                }
+
+                segments.push(Segment::Key(chars[start..pos].iter().collect()));
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "a": [1, 2, 3],
+            "empty": [],
+            "metadata": {"app.kubernetes.io/name": "web", "odd]key": true, "it's": "quoted"},
+            "nested": {"items": [{"name": "first"}, {"name": "last"}]},
+            "nothing": null
+        })
+    }
+
+    fn get(path: &str) -> Option<Value> {
+        query(&sample(), path).unwrap().cloned()
+    }
+
+    #[test]
+    fn keys_and_indexes() {
+        assert_eq!(get("$.a[0]"), Some(json!(1)));
+        assert_eq!(get("nested.items[1].name"), Some(json!("last")));
+        assert_eq!(get("$"), Some(sample()));
+        assert_eq!(get("$.nothing"), Some(Value::Null));
+    }
+
+    #[test]
+    fn negative_indexes_count_from_the_end() {
+        assert_eq!(get("$.a[-1]"), Some(json!(3)));
+        assert_eq!(get("$.a[-3]"), Some(json!(1)));
+        assert_eq!(get("$.nested.items[-1].name"), Some(json!("last")));
+    }
+
+    #[test]
+    fn out_of_range_is_missing() {
+        assert_eq!(get("$.a[3]"), None);
+        assert_eq!(get("$.a[-4]"), None);
+        assert_eq!(get("$.empty[0]"), None);
+        assert_eq!(get("$.empty[-1]"), None);
+        assert_eq!(get("$.a[18446744073709551615]"), None);
+    }
+
+    #[test]
+    fn wrong_type_is_missing() {
+        assert_eq!(get("$.metadata[0]"), None);
+        assert_eq!(get("$.a.length"), None);
+        assert_eq!(get("$.nothing.more"), None);
+        assert_eq!(get("$.missing.deeper[0]"), None);
+    }
+
+    #[test]
+    fn quoted_keys() {
+        assert_eq!(get(r#"$.metadata["app.kubernetes.io/name"]"#), Some(json!("web")));
+        assert_eq!(get("$.metadata['app.kubernetes.io/name']"), Some(json!("web")));
+        assert_eq!(get(r#"$.metadata[ "odd]key" ]"#), Some(json!(true)));
+        assert_eq!(get(r#"$.metadata["it's"]"#), Some(json!("quoted")));
+        assert_eq!(get(r#"$["a"][1]"#), Some(json!(2)));
+    }
+
+    #[test]
+    fn malformed_paths_are_errors() {
+        for path in ["$.a[0", "$.a[x]", "$.a[]", "$.a[-0]", "$.a[1.5]", r#"$.metadata["web]"#, r#"$.metadata["web"x]"#] {
+            assert!(query(&sample(), path).is_err(), "{path} should be an error");
+        }
+    }
+
+    #[test]
+    fn parses_segments() {
+        assert_eq!(
+            parse("$.a[-2]['b'].c").unwrap(),
+            vec![Segment::Key("a".to_string()), Segment::FromEnd(2), Segment::Key("b".to_string()), Segment::Key("c".to_string())]
+        );
+    }
+}
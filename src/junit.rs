@@ -0,0 +1,204 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use anyhow::{bail, Result};
+use glob::{glob, Pattern};
+use roxmltree::{Document, Node};
+use crate::test_results::{AssertResult, TestResult, TestStepResult};
+
+/// Loads every testcase from the JUnit XML files matching the pattern as a step result.
+/// Relative patterns are found from `base`. Files that haven't been written to since `since` are left over
+/// from an earlier run and are ignored.
+pub fn load_results(pattern: &str, base: &Path, since: SystemTime, row: Option<usize>) -> Result<Vec<TestStepResult>> {
+    let mut result = vec![];
+    let mut found = false;
+
+    let pattern = match Path::new(pattern).is_absolute() {
+        true => pattern.to_string(),
+        false => format!("{}/{pattern}", Pattern::escape(&base.to_string_lossy())),
+    };
+
+    for entry in glob(&pattern)? {
+        let path = entry?;
+
+        //File times can lag the clock slightly so allow a bit of slack.
+        let modified = fs::metadata(&path)?.modified()? + Duration::from_secs(1);
+        if modified < since {
+            continue;
+        }
+
+        found = true;
+
+        let text = fs::read_to_string(&path)?;
+        let doc = match Document::parse(&text) {
+            Ok(doc) => doc,
+            Err(e) => bail!("Unable to parse JUnit results {}: {e}", path.display()),
+        };
+
+        for case in doc.descendants().filter(|n| n.has_tag_name("testcase")) {
+            result.push(load_case(case, row));
+        }
+    }
+
+    if !found {
+        bail!("No JUnit results were written to {pattern}!");
+    }
+
+    Ok(result)
+}
+
+fn load_case(case: Node, row: Option<usize>) -> TestStepResult {
+    let name = case.attribute("name").unwrap_or("unnamed");
+
+    //Fall back to the name of the suite the case is in if it doesn't have a class.
+    let class = case.attribute("classname").or_else(|| {
+        case.ancestors()
+            .find(|n| n.has_tag_name("testsuite"))
+            .and_then(|n| n.attribute("name"))
+    });
+
+    let name = match class {
+        Some(class) if !class.is_empty() => format!("{class}.{name}"),
+        _ => name.to_string(),
+    };
+
+    let mut step = TestStepResult::new(Some(name), TestResult::Pass);
+    step.duration = case.attribute("time").and_then(|t| t.replace(',', "").parse().ok());
+
+    for child in case.children().filter(|n| n.is_element()) {
+        let tag = child.tag_name().name();
+
+        if tag == "failure" || tag == "error" {
+            step.result = TestResult::Fail;
+            step.asserts.push(AssertResult::new(&describe(child, tag), false, row));
+        }
+
+        if tag == "skipped" && step.result != TestResult::Fail {
+            step.result = TestResult::Skip;
+            step.asserts.push(AssertResult::new(&describe(child, tag), true, row));
+        }
+    }
+
+    step
+}
+
+fn describe(node: Node, tag: &str) -> String {
+    let message = node.attribute("message").map(|m| m.trim().to_string())
+        .or_else(|| node.text().map(|t| t.trim().to_string()))
+        .unwrap_or_default();
+
+    if message.is_empty() {
+        tag.to_string()
+    } else {
+        format!("{tag}: {message}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempPath;
+
+    fn parse(xml: &str) -> Vec<TestStepResult> {
+        let doc = Document::parse(xml).unwrap();
+        doc.descendants().filter(|n| n.has_tag_name("testcase")).map(|case| load_case(case, None)).collect()
+    }
+
+    fn messages(step: &TestStepResult) -> Vec<&str> {
+        step.asserts.iter().map(|a| a.message.as_str()).collect()
+    }
+
+    #[test]
+    fn nested_suites_use_the_closest_name() {
+        let cases = parse(r#"
+            <testsuites name="all">
+                <testsuite name="outer">
+                    <testcase name="top"/>
+                    <testsuite name="inner">
+                        <testcase name="deep"/>
+                    </testsuite>
+                </testsuite>
+            </testsuites>"#);
+
+        let names: Vec<_> = cases.iter().map(|c| c.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["outer.top", "inner.deep"]);
+    }
+
+    #[test]
+    fn classname_wins_unless_empty() {
+        let cases = parse(r#"
+            <testsuite name="suite">
+                <testcase classname="pkg.Class" name="a"/>
+                <testcase classname="" name="b"/>
+                <testcase/>
+            </testsuite>"#);
+
+        let names: Vec<_> = cases.iter().map(|c| c.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["pkg.Class.a", "b", "suite.unnamed"]);
+    }
+
+    #[test]
+    fn failures_and_errors() {
+        let cases = parse(r#"
+            <testsuite>
+                <testcase name="attribute"><failure message=" expected 1 "/></testcase>
+                <testcase name="text"><error>  boom  </error></testcase>
+                <testcase name="empty"><failure/></testcase>
+            </testsuite>"#);
+
+        assert!(cases.iter().all(|c| c.result == TestResult::Fail));
+        assert_eq!(messages(&cases[0]), vec!["failure: expected 1"]);
+        assert_eq!(messages(&cases[1]), vec!["error: boom"]);
+        assert_eq!(messages(&cases[2]), vec!["failure"]);
+    }
+
+    #[test]
+    fn failure_beats_skipped() {
+        let cases = parse(r#"
+            <testsuite>
+                <testcase name="first"><skipped/><failure message="no"/></testcase>
+                <testcase name="second"><failure message="no"/><skipped/></testcase>
+                <testcase name="third"><skipped message="later"/></testcase>
+            </testsuite>"#);
+
+        assert_eq!(cases[0].result, TestResult::Fail);
+        assert_eq!(cases[1].result, TestResult::Fail);
+        assert_eq!(messages(&cases[1]), vec!["failure: no"]);
+        assert_eq!(cases[2].result, TestResult::Skip);
+        assert_eq!(messages(&cases[2]), vec!["skipped: later"]);
+    }
+
+    #[test]
+    fn times() {
+        let cases = parse(r#"
+            <testsuite>
+                <testcase name="plain" time="0.25"/>
+                <testcase name="grouped" time="1,234.5"/>
+                <testcase name="junk" time="soon"/>
+            </testsuite>"#);
+
+        let times: Vec<_> = cases.iter().map(|c| c.duration).collect();
+        assert_eq!(times, vec![Some(0.25), Some(1234.5), None]);
+    }
+
+    #[test]
+    fn malformed_file_is_an_error() {
+        let file = TempPath::new("junit", ".xml");
+        fs::write(file.path(), "<testsuite><testcase name=\"a\">").unwrap();
+
+        let error = load_results(&file.path().to_string_lossy(), Path::new("."), SystemTime::UNIX_EPOCH, None).unwrap_err();
+        assert!(error.to_string().starts_with("Unable to parse JUnit results"));
+    }
+
+    #[test]
+    fn stale_and_missing_files_are_errors() {
+        let file = TempPath::new("junit", ".xml");
+        fs::write(file.path(), "<testsuite/>").unwrap();
+
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert!(load_results(&file.path().to_string_lossy(), Path::new("."), later, None).is_err());
+
+        let missing = TempPath::new("junit", ".xml");
+        assert!(load_results(&missing.path().to_string_lossy(), Path::new("."), SystemTime::UNIX_EPOCH, None).is_err());
+    }
+}
@@ -1,9 +1,10 @@
-use std::env::set_current_dir;
+use std::path::PathBuf;
 use crate::ui::{Ui, UIFormat};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use regex::Regex;
-use crate::runner::run;
-use crate::test_suite::{load_from_folder, load_values_file};
+use crate::process_tree::{cancel_all, interrupted};
+use crate::runner::{run, Budget, RunSettings};
+use crate::test_suite::{load_from_folder, load_values_file, HumanDuration};
 
 mod test_suite;
 mod runner;
@@ -11,12 +12,29 @@ mod test_results;
 pub mod ui;
 mod exec;
 mod environment;
+mod artifacts;
+mod temp;
+mod tap;
+mod junit;
+mod expect;
+mod snapshot;
+mod json_path;
+mod checks;
+mod shell;
+mod limits;
+mod run_as;
+mod process_tree;
 
 pub struct TestSession {
     pub ui_format: UIFormat,
     pub path: String,
     pub pattern: Option<String>,
     pub values_file: Option<String>,
+    pub artifacts_dir: Option<String>,
+    pub update_snapshots: bool,
+    pub verbose: bool,
+    /// How long the whole run can take, like `30m`.
+    pub timeout: Option<String>,
 }
 
 impl TestSession {
@@ -35,9 +53,43 @@ impl TestSession {
             None
         };
 
-        set_current_dir(&self.path)?;
+        let timeout = match &self.timeout {
+            Some(val) => Some(HumanDuration::parse(val)?),
+            None => None,
+        };
+
+        let settings = RunSettings {
+            artifacts_dir: self.artifacts_dir.as_ref().map(PathBuf::from),
+            update_snapshots: self.update_snapshots,
+            verbose: self.verbose,
+            budget: timeout.map(|t| Budget::new(t.0, "session")),
+        };
+
         let test_suites = load_from_folder(&self.path)?;
-        let result = run(&test_suites, &pattern, &values_file, &mut ui).await?;
+
+        //Steps run in their own process groups so a Ctrl-C doesn't reach them, they're stopped here instead. The
+        //run is kept until they have been, dropping it would kill them straight away.
+        let outcome = {
+            let running = run(&test_suites, &pattern, &values_file, &settings, &mut ui);
+            tokio::pin!(running);
+
+            tokio::select! {
+                result = &mut running => Ok(result?),
+                signal = interrupted() => Err((signal, cancel_all().await)),
+            }
+        };
+
+        let result = match outcome {
+            Ok(result) => result,
+            Err((signal, stopped)) => {
+                for message in stopped {
+                    ui.warning(&message).await?;
+                }
+
+                ui.close().await?;
+                bail!("Run cancelled by {signal}");
+            }
+        };
 
         ui.close().await?;
 
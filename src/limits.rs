@@ -0,0 +1,195 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::exec::Exit;
+
+/// Resource limits a step's command and everything it starts run with. Limits set on a step override the
+/// same limit set on the suite.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Address space each process can use, in megabytes.
+    pub memory_mb: Option<u64>,
+    /// CPU time each process can use, in seconds.
+    pub cpu_seconds: Option<u64>,
+    /// Files each process can have open at once.
+    pub open_files: Option<u64>,
+    /// Processes the user the step runs as can have, counting ones started outside the step. Not enforced for root.
+    pub processes: Option<u64>,
+}
+
+impl Limits {
+    /// The step's limits with any it doesn't set taken from the suite.
+    pub fn merge(step: Option<Limits>, suite: Option<Limits>) -> Option<Limits> {
+        match (step, suite) {
+            (Some(step), Some(suite)) => Some(Limits {
+                memory_mb: step.memory_mb.or(suite.memory_mb),
+                cpu_seconds: step.cpu_seconds.or(suite.cpu_seconds),
+                open_files: step.open_files.or(suite.open_files),
+                processes: step.processes.or(suite.processes),
+            }),
+            (step, suite) => step.or(suite),
+        }
+    }
+
+    /// Sets the limits on the current process. Runs in the child between fork and exec, so it only makes system calls.
+    #[cfg(unix)]
+    pub fn apply(&self) -> std::io::Result<()> {
+        if let Some(mb) = self.memory_mb {
+            set_limit(libc::RLIMIT_AS as _, mb.saturating_mul(1024 * 1024), 0)?;
+        }
+
+        //A second past the soft limit SIGXCPU turns into SIGKILL, so the command gets a chance to say why it stopped.
+        if let Some(seconds) = self.cpu_seconds {
+            set_limit(libc::RLIMIT_CPU as _, seconds, 1)?;
+        }
+
+        if let Some(files) = self.open_files {
+            set_limit(libc::RLIMIT_NOFILE as _, files, 0)?;
+        }
+
+        if let Some(processes) = self.processes {
+            set_limit(libc::RLIMIT_NPROC as _, processes, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Limits the command certainly ran into. Only the CPU time limit says so for sure, when the command itself was
+    /// stopped by it: SIGXCPU at the soft limit, or SIGKILL at the hard limit a second later if it ignored that. The
+    /// limit is for each process, so CPU time used by the command's children never counts towards it here.
+    pub fn exceeded(&self, exit: &Exit) -> Vec<String> {
+        let Some(seconds) = self.cpu_seconds else {
+            return vec![];
+        };
+
+        //The CPU time includes children the command waited for, but a kill for some other reason can't have used
+        //past the hard limit unless the command did.
+        let hard_limit = exit.signal() == Some(SIGKILL)
+            && exit.cpu_time.map(|t| t >= Duration::from_secs(seconds + 1)).unwrap_or(false);
+
+        match exit.signal() == Some(SIGXCPU) || hard_limit {
+            true => vec![format!("CPU time limit of {seconds}s")],
+            false => vec![],
+        }
+    }
+
+    /// Limits the command might have run into going by what it wrote to stderr. Programs say they've run out of
+    /// something in their own way and can say it for other reasons, so these are only worth a warning.
+    pub fn suspected(&self, stderr: &[String]) -> Vec<String> {
+        let stderr = stderr.join("\n").to_lowercase();
+        let said = |phrases: &[&str]| phrases.iter().any(|p| stderr.contains(p));
+        let mut result = vec![];
+
+        if let (Some(seconds), true) = (self.cpu_seconds, said(&["cpu time limit exceeded"])) {
+            result.push(format!("CPU time limit of {seconds}s"));
+        }
+
+        if let (Some(mb), true) = (self.memory_mb, said(&["cannot allocate memory", "memoryerror", "std::bad_alloc"])) {
+            result.push(format!("memory limit of {mb} MB"));
+        }
+
+        if let (Some(files), true) = (self.open_files, said(&["too many open files"])) {
+            result.push(format!("open files limit of {files}"));
+        }
+
+        if let (Some(processes), true) = (self.processes, said(&["cannot fork", "can't fork", "fork: retry"])) {
+            result.push(format!("processes limit of {processes}"));
+        }
+
+        result
+    }
+}
+
+#[cfg(unix)]
+const SIGXCPU: i32 = libc::SIGXCPU;
+#[cfg(unix)]
+const SIGKILL: i32 = libc::SIGKILL;
+
+//Nothing exits with a signal on Windows, so these never match.
+#[cfg(not(unix))]
+const SIGXCPU: i32 = -1;
+#[cfg(not(unix))]
+const SIGKILL: i32 = -2;
+
+/// Sets the soft limit and a hard limit `headroom` above it, keeping the hard limit where it is if we can't raise it.
+#[cfg(unix)]
+fn set_limit(resource: i32, value: u64, headroom: u64) -> std::io::Result<()> {
+    let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+
+    if unsafe { libc::getrlimit(resource as _, &mut current) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let value = value as libc::rlim_t;
+    let hard = value.saturating_add(headroom as libc::rlim_t);
+    let limit = libc::rlimit {
+        rlim_cur: value.min(current.rlim_max),
+        rlim_max: hard.min(current.rlim_max),
+    };
+
+    if unsafe { libc::setrlimit(resource as _, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::PathBuf;
+    use std::process::ExitStatus;
+    use super::*;
+    use crate::exec::{ExecOptions, RunningCommand};
+    use crate::shell::Invocation;
+    use crate::test_suite::{EnvMode, ProtocolChannel};
+
+    fn cpu_limit(seconds: u64) -> Limits {
+        Limits { cpu_seconds: Some(seconds), ..Limits::default() }
+    }
+
+    fn exit(status: i32, cpu: f64) -> Exit {
+        Exit { status: ExitStatus::from_raw(status), cpu_time: Some(Duration::from_secs_f64(cpu)) }
+    }
+
+    #[test]
+    fn signals_from_the_limit() {
+        assert_eq!(cpu_limit(2).exceeded(&exit(libc::SIGXCPU, 2.0)).len(), 1);
+        assert_eq!(cpu_limit(2).exceeded(&exit(libc::SIGKILL, 3.1)).len(), 1);
+    }
+
+    #[test]
+    fn other_exits_dont_count() {
+        //Children that used the time between them, each under the limit.
+        assert!(cpu_limit(2).exceeded(&exit(0, 3.6)).is_empty());
+        assert!(cpu_limit(2).exceeded(&exit(1 << 8, 3.6)).is_empty());
+        //Killed by something else before it could have reached the hard limit.
+        assert!(cpu_limit(2).exceeded(&exit(libc::SIGKILL, 1.5)).is_empty());
+        assert!(Limits::default().exceeded(&exit(libc::SIGXCPU, 9.0)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn several_children_under_the_limit() {
+        let options = ExecOptions {
+            timeout: Some(Duration::from_secs(30)),
+            channel: ProtocolChannel::Stdout,
+            cwd: PathBuf::from("."),
+            shell: None,
+            limits: Some(cpu_limit(1)),
+            env_mode: EnvMode::Inherit,
+            env_passthrough: vec![],
+            run_as: None,
+        };
+
+        //Three children each spinning for about 0.6s, well over the limit between them.
+        let command = "for n in 1 2 3; do timeout 0.6 sh -c 'while :; do :; done'; done; exit 0";
+        let invocation = Invocation::shell(Some("sh"), command).unwrap();
+        let mut exec = RunningCommand::new(invocation, &HashMap::new(), None, &options).unwrap();
+
+        while let Ok(Some(_)) = exec.next_line().await {}
+
+        let exit = exec.exit().await.unwrap();
+        assert!(exit.cpu_time.unwrap() > Duration::from_secs(1));
+        assert!(cpu_limit(1).exceeded(&exit).is_empty());
+    }
+}
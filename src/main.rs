@@ -3,7 +3,7 @@ use std::fs::metadata;
 use std::process::exit;
 use std::str::FromStr;
 use weave_test::ui::UIFormat;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use weave_test::TestSession;
 
 #[tokio::main]
@@ -13,7 +13,11 @@ async fn main() -> Result<()>{
         .arg(clap::arg!(--"values" <PATH> "Path to a settings file which will be passed to tests as environment variables."))
         .arg(clap::arg!(--"path" <PATH> "Path to where tests are running. Defaults to current directory."))
         .arg(clap::arg!(--"only" <PATTERN> "Only run tests in suites that match the regular expression."))
-        .arg(clap::arg!(--"format" <FORMAT> "Format output to the terminal. Can be colour, plain, none or json. Defaults to colour"));
+        .arg(clap::arg!(--"format" <FORMAT> "Format output to the terminal. Can be colour, plain, none or json. Defaults to colour"))
+        .arg(clap::arg!(--"artifacts-dir" <PATH> "Folder to copy files attached by tests into. Attached files are referenced in place if not set."))
+        .arg(clap::arg!(--"update-snapshots" "Rewrite stored snapshots with the output of this run instead of comparing against them."))
+        .arg(clap::arg!(--"timeout" <DURATION> "How long the whole run can take, like 90s, 5m or 1h30m. Anything still to run after that is marked as not run."))
+        .arg(clap::arg!(--"verbose" "Show more about how each step is run, like the environment its command sees. Only the names of variables inherited from our own environment are shown."));
     let matches = cmd.get_matches();
 
     //Getting tests folder.
@@ -31,6 +35,18 @@ async fn main() -> Result<()>{
     let ui_format = UIFormat::from_str(ui_format.as_str())?;
 
     let data_file = matches.get_one::<String>("values").cloned();
+    let update_snapshots = matches.get_flag("update-snapshots");
+    let verbose = matches.get_flag("verbose");
+    let timeout = matches.get_one::<String>("timeout").cloned();
+
+    //Made absolute so attachments end up in the same folder whichever folder a step runs in.
+    let artifacts_dir = matches.get_one::<String>("artifacts-dir")
+        .map(|dir| -> Result<String> {
+            fs::create_dir_all(dir).map_err(|e| anyhow!("Unable to create artifacts folder {dir}: {e}"))?;
+            let dir = fs::canonicalize(dir).map_err(|e| anyhow!("Unable to resolve artifacts folder {dir}: {e}"))?;
+            Ok(dir.to_string_lossy().to_string())
+        })
+        .transpose()?;
 
     if !meta.is_dir() {
         eprintln!("Expected a folder to be passed in as the test path!");
@@ -49,6 +65,10 @@ async fn main() -> Result<()>{
         path,
         pattern,
         values_file: data_file,
+        artifacts_dir,
+        update_snapshots,
+        verbose,
+        timeout,
     };
 
     session.run().await?;
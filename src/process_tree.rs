@@ -0,0 +1,312 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{bail, Result};
+use tokio::process::Child;
+use tokio::time::sleep;
+
+/// How long a stopped command gets to exit after SIGTERM before it is killed.
+const TERM_GRACE: Duration = Duration::from_secs(3);
+
+/// How often a stopped command's processes are checked for having exited.
+const STOP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Commands that are running, so they can be stopped if weave-test is told to stop.
+static RUNNING: Mutex<Vec<Arc<ProcessTree>>> = Mutex::new(Vec::new());
+
+/// Set once weave-test has been told to stop, after which no more commands are started.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// A command and everything it started, so they can be stopped together. This is a process group on unix, the
+/// child makes its own in `pre_exec`, and a job object on Windows.
+pub struct ProcessTree {
+    #[cfg(unix)]
+    pgid: u32,
+    #[cfg(windows)]
+    job: Job,
+}
+
+enum Signal {
+    Term,
+    Kill,
+}
+
+impl ProcessTree {
+    #[cfg(unix)]
+    pub fn new(child: &Child) -> Result<ProcessTree> {
+        match child.id() {
+            Some(pgid) => Ok(ProcessTree { pgid }),
+            None => bail!("Command exited before it could be watched"),
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn new(child: &Child) -> Result<ProcessTree> {
+        let Some(handle) = child.raw_handle() else {
+            bail!("Command exited before it could be watched");
+        };
+
+        let job = Job::new()?;
+        job.assign(handle)?;
+
+        Ok(ProcessTree { job })
+    }
+
+    /// Kills everything straight away, for when nothing the command started is wanted any more.
+    pub fn kill(&self) {
+        self.signal(Signal::Kill);
+    }
+
+    /// Stops everything, asking nicely with SIGTERM before killing whatever is left after a grace period. Returns a
+    /// line for each process that had to be killed or is still running afterwards.
+    pub async fn stop(&self) -> Vec<String> {
+        self.signal(Signal::Term);
+
+        let deadline = Instant::now() + TERM_GRACE;
+
+        while !self.members().is_empty() && Instant::now() < deadline {
+            sleep(STOP_INTERVAL).await;
+        }
+
+        let stubborn = self.members();
+
+        if stubborn.is_empty() {
+            return vec![];
+        }
+
+        self.signal(Signal::Kill);
+        sleep(STOP_INTERVAL).await;
+
+        let survivors = self.members();
+
+        stubborn.iter()
+            .map(|p| match survivors.contains(p) {
+                true => format!("{p} is still running after being killed"),
+                false => format!("{p} didn't stop within {}s of SIGTERM and was killed", TERM_GRACE.as_secs()),
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: Signal) {
+        let signal = match signal {
+            Signal::Term => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        };
+
+        unsafe { libc::killpg(self.pgid as libc::pid_t, signal) };
+    }
+
+    /// Windows has no SIGTERM for console programs, so the whole job is ended straight away.
+    #[cfg(windows)]
+    fn signal(&self, _signal: Signal) {
+        self.job.terminate();
+    }
+
+    /// Processes still running, as `name (pid N)`. Zombies waiting to be reaped don't count.
+    #[cfg(target_os = "linux")]
+    pub fn members(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir("/proc") else {
+            return vec![];
+        };
+
+        let mut result = vec![];
+
+        for entry in entries.flatten() {
+            let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+                continue;
+            };
+
+            let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+                continue;
+            };
+
+            //The name is in brackets and can contain spaces, the fields after it are state, ppid and pgrp.
+            let (Some(open), Some(close)) = (stat.find('('), stat.rfind(')')) else {
+                continue;
+            };
+
+            let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+
+            if fields.len() > 2 && fields[0] != "Z" && fields[2] == self.pgid.to_string() {
+                result.push(format!("{} (pid {pid})", &stat[open + 1..close]));
+            }
+        }
+
+        result
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn members(&self) -> Vec<String> {
+        let Ok(output) = std::process::Command::new("ps").args(["-A", "-o", "pid=,pgid=,stat=,comm="]).output() else {
+            return vec![];
+        };
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+
+                match fields.as_slice() {
+                    [pid, group, stat, name @ ..] if *group == self.pgid.to_string() && !stat.starts_with('Z') => {
+                        Some(format!("{} (pid {pid})", name.join(" ")))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(windows)]
+    pub fn members(&self) -> Vec<String> {
+        self.job.processes().iter().map(|pid| format!("pid {pid}")).collect()
+    }
+}
+
+/// Adds a command to the ones stopped if weave-test is told to stop. Fails if it already has been.
+pub fn register(tree: &Arc<ProcessTree>) -> Result<()> {
+    if CANCELLED.load(Ordering::SeqCst) {
+        tree.kill();
+        bail!("The run was cancelled");
+    }
+
+    RUNNING.lock().unwrap().push(tree.clone());
+    Ok(())
+}
+
+pub fn unregister(tree: &Arc<ProcessTree>) {
+    RUNNING.lock().unwrap().retain(|t| !Arc::ptr_eq(t, tree));
+}
+
+/// Whether weave-test has been told to stop.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Stops every command that is running the same way a timed out step is stopped, and any started afterwards fail
+/// to start. Returns a line for each process that had to be killed.
+pub async fn cancel_all() -> Vec<String> {
+    CANCELLED.store(true, Ordering::SeqCst);
+
+    let trees: Vec<Arc<ProcessTree>> = RUNNING.lock().unwrap().drain(..).collect();
+    let stops: Vec<_> = trees.into_iter().map(|tree| tokio::spawn(async move { tree.stop().await })).collect();
+    let mut result = vec![];
+
+    for stop in stops {
+        if let Ok(messages) = stop.await {
+            result.extend(messages);
+        }
+    }
+
+    result
+}
+
+/// Waits for weave-test to be told to stop with Ctrl-C, or SIGTERM on unix, and returns which it was.
+pub async fn interrupted() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            return tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM",
+            };
+        }
+    }
+
+    match tokio::signal::ctrl_c().await {
+        Ok(_) => "Ctrl-C",
+        //Without a handler we can never be interrupted.
+        Err(_) => std::future::pending().await,
+    }
+}
+
+/// A job object that kills everything in it once the last handle to it is closed.
+#[cfg(windows)]
+struct Job(windows_sys::Win32::Foundation::HANDLE);
+
+//Job handles can be used from any thread.
+#[cfg(windows)]
+unsafe impl Send for Job {}
+#[cfg(windows)]
+unsafe impl Sync for Job {}
+
+#[cfg(windows)]
+impl Job {
+    fn new() -> Result<Job> {
+        use windows_sys::Win32::System::JobObjects::*;
+
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+
+        if handle == 0 {
+            bail!("Unable to create job object: {}", std::io::Error::last_os_error());
+        }
+
+        let job = Job(handle);
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+
+        let set = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const std::ffi::c_void,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        };
+
+        if set == 0 {
+            bail!("Unable to set up job object: {}", std::io::Error::last_os_error());
+        }
+
+        Ok(job)
+    }
+
+    fn assign(&self, process: std::os::windows::io::RawHandle) -> Result<()> {
+        if unsafe { windows_sys::Win32::System::JobObjects::AssignProcessToJobObject(self.0, process as _) } == 0 {
+            bail!("Unable to add command to job object: {}", std::io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn terminate(&self) {
+        unsafe { windows_sys::Win32::System::JobObjects::TerminateJobObject(self.0, 1) };
+    }
+
+    fn processes(&self) -> Vec<usize> {
+        use windows_sys::Win32::System::JobObjects::*;
+
+        #[repr(C)]
+        struct ProcessIds {
+            _assigned: u32,
+            listed: u32,
+            ids: [usize; 256],
+        }
+
+        let mut list = ProcessIds { _assigned: 0, listed: 0, ids: [0; 256] };
+
+        let ok = unsafe {
+            QueryInformationJobObject(
+                self.0,
+                JobObjectBasicProcessIdList,
+                &mut list as *mut _ as *mut std::ffi::c_void,
+                std::mem::size_of::<ProcessIds>() as u32,
+                std::ptr::null_mut(),
+            )
+        };
+
+        match ok {
+            0 => vec![],
+            _ => list.ids[..(list.listed as usize).min(256)].to_vec(),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        unsafe { windows_sys::Win32::Foundation::CloseHandle(self.0) };
+    }
+}
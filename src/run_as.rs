@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::{bail, Result};
+use crate::checks::accounts;
+
+/// The user and groups a step's command is run as.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups, the ones the user is a member of plus the primary group.
+    pub groups: Vec<u32>,
+    pub home: String,
+}
+
+impl Identity {
+    /// Looks up `user` or `user:group`, where either can be a name or a numeric id.
+    pub fn resolve(spec: &str) -> Result<Identity> {
+        let (user, group) = match spec.split_once(':') {
+            Some((user, group)) => (user.trim(), Some(group.trim())),
+            None => (spec.trim(), None),
+        };
+
+        let users = accounts::users()?;
+        let groups = accounts::groups()?;
+
+        let (name, uid, primary, home) = match users.iter().find(|u| u.name == user || u.uid.to_string() == user) {
+            Some(found) => (found.name.clone(), found.uid, found.gid, found.home.clone()),
+            None => match user.parse::<u32>() {
+                //An id without an account still works, it just has nowhere to call home.
+                Ok(uid) => (user.to_string(), uid, uid, "/".to_string()),
+                Err(_) => bail!("run_as user {user} doesn't exist"),
+            },
+        };
+
+        let gid = match group {
+            None => primary,
+            Some(group) => match groups.iter().find(|g| g.name == group || g.gid.to_string() == group) {
+                Some(found) => found.gid,
+                None => match group.parse::<u32>() {
+                    Ok(gid) => gid,
+                    Err(_) => bail!("run_as group {group} doesn't exist"),
+                },
+            },
+        };
+
+        let mut member_of: Vec<u32> = groups.iter().filter(|g| g.members.contains(&name)).map(|g| g.gid).collect();
+        member_of.push(gid);
+        member_of.sort();
+        member_of.dedup();
+
+        Ok(Identity { name, uid, gid, groups: member_of, home })
+    }
+
+    /// Variables the command would have if the user had logged in.
+    pub fn env(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("HOME".to_string(), self.home.clone()),
+            ("USER".to_string(), self.name.clone()),
+            ("LOGNAME".to_string(), self.name.clone()),
+        ])
+    }
+
+    /// Fails unless weave-test is able to switch to the user, which needs root unless it's already them.
+    #[cfg(unix)]
+    pub fn check_privilege(&self) -> Result<()> {
+        let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+
+        if euid != 0 && (euid != self.uid || egid != self.gid) {
+            bail!("run_as {} needs weave-test to run as root, it's running as {}", self.name, accounts::user_name(euid));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn check_privilege(&self) -> Result<()> {
+        bail!("run_as is not supported on this platform!");
+    }
+
+    /// Hands a file weave-test made for the command, like its script, over to the user so they can use it.
+    #[cfg(unix)]
+    pub fn give(&self, path: &Path) -> Result<()> {
+        std::os::unix::fs::chown(path, Some(self.uid), Some(self.gid))?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn give(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Drops to the user's groups then the user. Runs in the child between fork and exec, so it only makes system calls.
+    /// The order matters, once the uid has changed we're no longer allowed to change groups.
+    #[cfg(unix)]
+    pub fn apply(&self) -> std::io::Result<()> {
+        unsafe {
+            //Only root can change groups, anyone else is already who they're asked to be.
+            if libc::geteuid() != 0 {
+                return Ok(());
+            }
+
+            if libc::setgroups(self.groups.len() as _, self.groups.as_ptr()) != 0
+                || libc::setgid(self.gid) != 0
+                || libc::setuid(self.uid) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
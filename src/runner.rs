@@ -1,27 +1,73 @@
 use std::collections::HashMap;
-use std::time::Duration;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use regex::Regex;
-use crate::test_results::{AssertResult, TestResult, TestStepResult, TestSuiteResult};
-use crate::test_suite::{TestStep, TestSuite, ValuesFile};
+use tokio::time::sleep;
+use crate::test_results::{Artifact, AssertResult, TestResult, TestStepResult, TestSuiteResult, Warning};
+use crate::test_suite::{Expectations, HumanDuration, LimitAction, Protocol, ProtocolChannel, TestStep, TestSuite, ValuesFile};
 use crate::ui::Ui;
 use anyhow::Result;
-use crate::environment::HashMapExt;
-use crate::exec::RunningCommand;
+use crate::environment::{effective, interpolate, HashMapExt};
+use crate::exec::{CommandOutput, ExecOptions, OutputLine, RunningCommand};
+use crate::artifacts;
+use crate::tap::{TapEvent, TapParser};
+use crate::junit;
+use crate::expect;
+use crate::snapshot::Snapshot;
+use crate::checks;
+use crate::limits::Limits;
+use crate::run_as::Identity;
+use crate::shell::Invocation;
+
+/// Time each run of a step's command gets if it doesn't set a timeout.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A deadline shared by several steps, and what to call it when it's used up.
+#[derive(Clone)]
+pub struct Budget {
+    pub ends: Instant,
+    pub name: String,
+}
+
+impl Budget {
+    pub fn new(duration: Duration, kind: &str) -> Budget {
+        Budget {
+            ends: Instant::now() + duration,
+            name: format!("{kind} timeout of {}", HumanDuration(duration)),
+        }
+    }
+}
+
+/// Session wide settings that change how steps are run.
+pub struct RunSettings {
+    pub artifacts_dir: Option<PathBuf>,
+    pub update_snapshots: bool,
+    /// Show more about how each step is run, like the environment its command sees.
+    pub verbose: bool,
+    /// The session's `--timeout`, once it's used up everything still to run is marked as not run.
+    pub budget: Option<Budget>,
+}
 
 struct TestSuiteRunner<'a> {
     suite: &'a TestSuite,
     ui: &'a mut Ui,
     value_file: &'a Option<ValuesFile>,
+    settings: &'a RunSettings,
     data_sets: HashMap<String, Vec<HashMap<String, String>>>,
+    /// Whichever of the suite and session timeouts ends first.
+    budget: Option<Budget>,
 }
 
 impl TestSuiteRunner<'_> {
-    fn new<'a>(suite: &'a TestSuite, ui: &'a mut Ui, value_file: &'a Option<ValuesFile>) -> TestSuiteRunner<'a> {
+    fn new<'a>(suite: &'a TestSuite, ui: &'a mut Ui, value_file: &'a Option<ValuesFile>, settings: &'a RunSettings) -> TestSuiteRunner<'a> {
         TestSuiteRunner{
             suite,
             ui,
             value_file,
+            settings,
             data_sets: HashMap::new(),
+            budget: None,
         }
     }
 
@@ -61,18 +107,33 @@ impl TestSuiteRunner<'_> {
         }
 
 
+        let suite_budget = self.suite.timeout.map(|t| Budget::new(t.0, "suite"));
+        self.budget = [suite_budget, self.settings.budget.clone()].into_iter().flatten().min_by_key(|b| b.ends);
+
         self.ui.start_suite(&self.suite.name).await?;
 
         let mut failed = false;
-        for step in &self.suite.steps {
+        let mut out_of_time = false;
+        for (index, step) in self.suite.steps.iter().enumerate() {
+
+            if !failed && !out_of_time && self.time_left() == Some(Duration::ZERO) {
+                out_of_time = true;
+
+                if let Some(budget) = &self.budget {
+                    self.ui.warning(&format!("The {} is used up, the remaining steps won't be run", budget.name)).await?;
+                }
+
+                //A suite that never got to start didn't run, one cut short can't say whether it passed.
+                result.overall_result = match index {
+                    0 => TestResult::NotRun,
+                    _ if result.overall_result == TestResult::Fail => TestResult::Fail,
+                    _ => TestResult::Inconclusive,
+                };
+            }
 
             //Setting status of all remaining tests as not run.
-            if failed {
-                result.steps.push(TestStepResult{
-                    name: step.name.clone(),
-                    result: TestResult::NotRun,
-                    asserts: vec![],
-                });
+            if failed || out_of_time {
+                result.steps.push(TestStepResult::new(step.name.clone(), TestResult::NotRun));
 
                 let step_name = step.name.clone().unwrap_or("Unnamed".to_string());
 
@@ -84,11 +145,7 @@ impl TestSuiteRunner<'_> {
 
             if let Some(v) = &step.skip  {
                 if *v {
-                    result.steps.push(TestStepResult{
-                        name: step.name.clone(),
-                        result: TestResult::Skip,
-                        asserts: vec![],
-                    });
+                    result.steps.push(TestStepResult::new(step.name.clone(), TestResult::Skip));
                 }
 
                 let step_name = step.name.clone().unwrap_or("Unnamed".to_string());
@@ -100,7 +157,7 @@ impl TestSuiteRunner<'_> {
             }
 
             //let step_result = run_step(step, &self.suite.env, &value_env, &data_sets, self.ui).await?;
-            let step_result = self.run_step(step).await?;
+            let step_result = self.run_step(step, index).await?;
 
             if step_result.result == TestResult::Fail {
                 failed = true;
@@ -119,55 +176,108 @@ impl TestSuiteRunner<'_> {
         Ok(result)
     }
 
-    async fn run_step(&mut self, step: &TestStep) -> Result<TestStepResult> {
+    async fn run_step(&mut self, step: &TestStep, index: usize) -> Result<TestStepResult> {
 
-        let mut result = TestStepResult{
-            name: step.name.clone(),
-            result: TestResult::Inconclusive,
-            asserts: vec![],
-        };
+        let mut result = TestStepResult::new(step.name.clone(), TestResult::Inconclusive);
 
         let mut env: HashMap<String, String> = HashMap::new();
-        let time_out = if let Some(time) = step.timeout {
-            time
-        }else{
-            300
+        let time_out = step.timeout.map(|t| t.0).unwrap_or(DEFAULT_TIMEOUT);
+        let name = step.name.clone().unwrap_or("".to_string());
+        let step_started = Instant::now();
+
+        self.ui.start_step(&name).await?;
+
+        let run_as = match step.run_as.as_ref().or(self.suite.run_as.as_ref()) {
+            Some(spec) => match Identity::resolve(spec).and_then(|identity| identity.check_privilege().map(|_| identity)) {
+                Ok(identity) => Some(identity),
+                Err(e) => {
+                    let message = e.to_string();
+                    self.ui.assert(&message, false).await?;
+                    result.asserts.push(AssertResult::new(&message, false, None));
+                    result.result = TestResult::Fail;
+                    result.duration = Some(step_started.elapsed().as_secs_f64());
+                    self.ui.finish_step(&name, result.result.clone()).await?;
+                    return Ok(result);
+                }
+            },
+            None => None,
         };
 
+        //The user's own HOME and the like, unless the suite or step say otherwise.
+        if let Some(identity) = &run_as {
+            env.append(&identity.env());
+        }
+
         env.try_append(&self.suite.env);
         env.try_append(&step.env);
         if let Some(value) = self.value_file {
             env.append(&value.env);
         }
 
-        let name = step.name.clone().unwrap_or("".to_string());
-
-        self.ui.start_step(&name).await?;
+        let options = ExecOptions {
+            timeout: Some(time_out),
+            channel: step.channel.clone()
+                .or(self.suite.channel.clone())
+                .unwrap_or(ProtocolChannel::Stdout),
+            cwd: self.working_dir(step),
+            shell: step.shell.clone().or(self.suite.shell.clone()),
+            limits: Limits::merge(step.limits, self.suite.limits),
+            env_mode: step.env_mode.clone().or(self.suite.env_mode.clone()).unwrap_or_default(),
+            env_passthrough: step.env_passthrough.clone().or(self.suite.env_passthrough.clone()).unwrap_or_default(),
+            run_as,
+        };
 
         if let Some(set_name)  = &step.data_set {
-            let data_set = self.data_sets.get(set_name).unwrap();
+            let data_set = self.data_sets.get(set_name).unwrap().clone();
             self.ui.start_set(set_name).await?;
 
             result.result = TestResult::NotRun;
+            let mut out_of_time = false;
 
             for (idx, row) in data_set.iter().enumerate() {
+                //Rows still to run once the suite or session is out of time are left as not run.
+                if self.time_left() == Some(Duration::ZERO) {
+                    if let (Some(budget), false) = (&self.budget, out_of_time) {
+                        out_of_time = true;
+                        let message = format!("The {} is used up, the remaining rows won't be run", budget.name);
+                        self.ui.warning(&message).await?;
+                        result.warnings.push(Warning { message, data_set_row: Some(idx) });
+
+                        //Rows that ran can't speak for the ones that didn't.
+                        if result.result == TestResult::Pass {
+                            result.result = TestResult::Inconclusive;
+                        }
+                    }
+
+                    result.row_results.push(TestResult::NotRun);
+                    continue;
+                }
+
                 self.ui.report_set_row(idx).await?;
 
                 let mut set_env = env.clone();
                 set_env.append(row);
 
-                let run_result = if let Ok(r) = execute_command(&step.command, set_env, self.ui, &mut result.asserts, Some(idx), time_out).await {
+                let row_started = Instant::now();
+                let run_result = if let Ok(r) = self.execute_attempts(step, index, Some(idx), set_env, &options, &mut result).await {
                     r
                 }else{
                     self.ui.assert("Test Timeout Hit", false).await?;
-                    result.asserts.push(AssertResult{
-                        message: "Test timed out!".to_string(),
-                        success: false,
-                        data_set_row: Some(idx),
-                    });
+                    result.asserts.push(AssertResult::new("Test timed out!", false, Some(idx)));
+                    TestResult::Fail
+                };
+
+                let elapsed = row_started.elapsed();
+                result.row_durations.push(elapsed.as_secs_f64());
+
+                let run_result = if check_duration(step, elapsed, Some(idx), self.ui, &mut result).await? {
+                    run_result
+                } else {
                     TestResult::Fail
                 };
 
+                result.row_results.push(run_result.clone());
+
                 if run_result == TestResult::Pass && result.result != TestResult::Fail && result.result != TestResult::Inconclusive {
                     result.result = TestResult::Pass;
                 }
@@ -185,26 +295,173 @@ impl TestSuiteRunner<'_> {
 
         } else{
 
-            result.result = if let Ok(r) = execute_command(&step.command, env, self.ui, &mut result.asserts, None, time_out).await {
+            result.result = if let Ok(r) = self.execute_attempts(step, index, None, env, &options, &mut result).await {
                 r
             }else{
                 self.ui.assert("Test Timeout Hit", false).await?;
-                result.asserts.push(AssertResult{
-                    message: "Test timed out!".to_string(),
-                    success: false,
-                    data_set_row: None,
-                });
+                result.asserts.push(AssertResult::new("Test timed out!", false, None));
                 TestResult::Fail
             };
+
+            if !check_duration(step, step_started.elapsed(), None, self.ui, &mut result).await? {
+                result.result = TestResult::Fail;
+            }
         }
 
+        result.duration = Some(step_started.elapsed().as_secs_f64());
+
         self.ui.finish_step(&name, result.result.clone()).await?;
 
         Ok(result)
     }
+
+    /// Time left before the suite or session timeout is used up, if there is one.
+    fn time_left(&self) -> Option<Duration> {
+        self.budget.as_ref().map(|b| b.ends.saturating_duration_since(Instant::now()))
+    }
+
+    /// The options with the command's timeout cut short if the suite or session has less time left than that.
+    fn bounded(&self, options: &ExecOptions) -> ExecOptions {
+        let mut options = options.clone();
+
+        if let Some(left) = self.time_left() {
+            options.timeout = Some(options.timeout.map_or(left, |t| t.min(left)));
+        }
+
+        options
+    }
+
+    /// Folder a step runs in, its own `cwd` or the suite's, relative to the suite file.
+    fn working_dir(&self, step: &TestStep) -> PathBuf {
+        let suite_dir = self.suite.path.parent().unwrap_or(Path::new("."));
+
+        match step.cwd.as_ref().or(self.suite.cwd.as_ref()) {
+            Some(cwd) => suite_dir.join(cwd),
+            None => suite_dir.to_path_buf(),
+        }
+    }
+
+    /// Runs a step once, or with `wait_until` again and again until it passes or runs out of time.
+    /// Only the last attempt is shown and kept in the results.
+    async fn execute_attempts(&mut self, step: &TestStep, index: usize, row: Option<usize>, env: HashMap<String, String>, options: &ExecOptions, result: &mut TestStepResult) -> Result<TestResult> {
+        let set = env;
+        let env = effective(&options.env_mode, &options.env_passthrough, &set);
+
+        if self.settings.verbose {
+            match step.actions().first() {
+                Some(&("command" | "args" | "script")) => {
+                    //Inherited variables can hold secrets the suite never asked for, so only their names are shown.
+                    let mut lines: Vec<String> = env.iter()
+                        .map(|(k, v)| match set.contains_key(k) {
+                            true => format!("  {k}={v}"),
+                            false => format!("  {k} (inherited)"),
+                        })
+                        .collect();
+                    lines.sort();
+                    self.ui.print(&format!("Environment:\n{}", lines.join("\n"))).await?;
+                }
+                Some(check) => {
+                    self.ui.print(&format!("No environment to show, {check} checks don't run a command")).await?;
+                }
+                None => {}
+            }
+        }
+
+        let Some(wait) = &step.wait_until else {
+            let context = self.step_context(step, index, row);
+            return execute_step(step, env, &self.bounded(options), context, self.ui, result).await;
+        };
+
+        let within = wait.within.0;
+        let interval = wait.interval.map(|i| i.0).unwrap_or(Duration::from_secs(1));
+        let mut deadline = Instant::now() + within;
+
+        if let Some(budget) = &self.budget {
+            deadline = deadline.min(budget.ends);
+        }
+
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+
+            let mut attempt = TestStepResult::new(step.name.clone(), TestResult::Inconclusive);
+            let context = self.step_context(step, index, row);
+
+            //An attempt can't run on past the point we'd stop waiting for it.
+            let mut attempt_options = self.bounded(options);
+            let left = deadline.saturating_duration_since(Instant::now());
+            attempt_options.timeout = Some(attempt_options.timeout.map_or(left, |t| t.min(left)));
+
+            self.ui.record();
+            let run = execute_step(step, env.clone(), &attempt_options, context, self.ui, &mut attempt).await;
+            let output = self.ui.take_recording();
+
+            let passed = matches!(run, Ok(TestResult::Pass));
+
+            if !passed && Instant::now() + interval < deadline {
+                sleep(interval).await;
+                continue;
+            }
+
+            self.ui.play(output).await?;
+
+            result.asserts.extend(attempt.asserts);
+            result.artifacts.extend(attempt.artifacts);
+            result.sub_steps.extend(attempt.sub_steps);
+            result.warnings.extend(attempt.warnings);
+
+            if attempt.interpreter.is_some() {
+                result.interpreter = attempt.interpreter;
+            }
+
+            result.attempts.push(attempts);
+
+            if passed {
+                self.ui.print(&format!("Passed after {attempts} attempts")).await?;
+            } else {
+                self.ui.print(&format!("Still failing after {attempts} attempts in {}", wait.within)).await?;
+            }
+
+            return run;
+        }
+    }
+
+    fn step_context(&self, step: &TestStep, index: usize, row: Option<usize>) -> StepContext {
+        StepContext {
+            row,
+            artifact_dir: self.artifact_dir(step, index, row),
+            protocol: step.protocol.clone().unwrap_or(Protocol::Weave),
+            junit_results: step.junit_results.clone(),
+            expect: step.expect.clone(),
+            snapshot: step.snapshot.as_ref().map(|name| Snapshot::new(
+                &self.suite.path,
+                name,
+                row,
+                step.snapshot_redact.clone().unwrap_or_default(),
+                self.settings.update_snapshots,
+            )),
+        }
+    }
+
+    /// Folder attachments for a step (and data set row) are copied into: `<artifacts>/<suite>/<step>/<row>`.
+    fn artifact_dir(&self, step: &TestStep, index: usize, row: Option<usize>) -> Option<PathBuf> {
+        let root = self.settings.artifacts_dir.as_ref()?;
+        let step_name = step.name.clone().unwrap_or("step".to_string());
+
+        let row = match row {
+            Some(idx) => format!("row-{idx}"),
+            None => "run".to_string(),
+        };
+
+        Some(root
+            .join(artifacts::sanitize(&self.suite.name))
+            .join(format!("{:03}_{}", index + 1, artifacts::sanitize(&step_name)))
+            .join(row))
+    }
 }
 
-pub async fn run(test_suites: &[TestSuite], pattern: &Option<Regex>, value_file: &Option<ValuesFile>, ui: &mut Ui) -> Result<Vec<TestSuiteResult>> {
+pub async fn run(test_suites: &[TestSuite], pattern: &Option<Regex>, value_file: &Option<ValuesFile>, settings: &RunSettings, ui: &mut Ui) -> Result<Vec<TestSuiteResult>> {
     let mut result: Vec<TestSuiteResult> = vec![];
 
     for suite in test_suites.iter() {
@@ -213,7 +470,7 @@ pub async fn run(test_suites: &[TestSuite], pattern: &Option<Regex>, value_file:
                 continue;
             }
         }
-        let mut runner = TestSuiteRunner::new(suite, ui, value_file);
+        let mut runner = TestSuiteRunner::new(suite, ui, value_file, settings);
         result.push(runner.run().await?)
 
     }
@@ -222,64 +479,396 @@ pub async fn run(test_suites: &[TestSuite], pattern: &Option<Regex>, value_file:
 }
 
 
-async fn execute_command(command: &String, environment: HashMap<String, String>, ui: &mut Ui, asserts: &mut Vec<AssertResult>, row: Option<usize>, timeout: u64) -> Result<TestResult> {
-    let mut exec = RunningCommand::new(command, &environment, Some(Duration::from_secs(timeout)))?;
-    let mut result = TestResult::Inconclusive;
+/// Fails, or warns about, a run that took longer than the step's max duration. Returns false if it failed.
+async fn check_duration(step: &TestStep, elapsed: Duration, row: Option<usize>, ui: &mut Ui, result: &mut TestStepResult) -> Result<bool> {
+    let Some(limit) = step.max_duration else {
+        return Ok(true);
+    };
+
+    if elapsed <= limit.0 {
+        return Ok(true);
+    }
+
+    let message = format!("Took {:.2}s, longer than the max duration of {limit}", elapsed.as_secs_f64());
+
+    match step.max_duration_action.clone().unwrap_or(LimitAction::Fail) {
+        LimitAction::Fail => {
+            ui.assert(&message, false).await?;
+            result.asserts.push(AssertResult::new(&message, false, row));
+            Ok(false)
+        }
+        LimitAction::Warn => {
+            ui.warning(&message).await?;
+            result.warnings.push(Warning { message, data_set_row: row });
+            Ok(true)
+        }
+    }
+}
+
+/// Per run details of a step that aren't needed to start the command itself.
+struct StepContext {
+    row: Option<usize>,
+    artifact_dir: Option<PathBuf>,
+    protocol: Protocol,
+    junit_results: Option<String>,
+    expect: Option<Expectations>,
+    snapshot: Option<Snapshot>,
+}
+
+async fn execute_step(step: &TestStep, environment: HashMap<String, String>, options: &ExecOptions, context: StepContext, ui: &mut Ui, step_result: &mut TestStepResult) -> Result<TestResult> {
+    if !options.cwd.is_dir() {
+        let mut recorder = AssertRecorder::new(context.row);
+        recorder.assert(&format!("Working directory {} exists", options.cwd.display()), false, ui, step_result).await?;
+        return Ok(recorder.result);
+    }
+
+    let invocation = match (&step.args, &step.command, &step.script) {
+        (Some(args), _, _) => Some(Invocation::direct(args)),
+        (None, Some(command), _) => Some(Invocation::shell(options.shell.as_deref(), command)),
+        (None, None, Some(script)) => Some(Invocation::script(options.shell.as_deref(), script)),
+        (None, None, None) => None,
+    };
+
+    if let Some(invocation) = invocation {
+        let stdin = match (&step.stdin, &step.stdin_file) {
+            (Some(text), _) => Some(interpolate(text, &environment).into_bytes()),
+            (None, Some(file)) => match fs::read(options.cwd.join(file)) {
+                Ok(data) => match String::from_utf8(data) {
+                    Ok(text) => Some(interpolate(&text, &environment).into_bytes()),
+                    Err(e) => Some(e.into_bytes()),
+                },
+                Err(e) => {
+                    let mut recorder = AssertRecorder::new(context.row);
+                    recorder.assert(&format!("stdin_file {file} can be read\n  {e}"), false, ui, step_result).await?;
+                    return Ok(recorder.result);
+                }
+            },
+            (None, None) => None,
+        };
+
+        return execute_command(invocation, environment, stdin, options, context, ui, step_result).await;
+    }
+
+    let time_out = options.timeout.unwrap_or(Duration::MAX);
+    let checks = checks::run(step, &options.cwd, time_out, &environment).await
+        .unwrap_or(vec![("Step doesn't have a command or check to run!".to_string(), false)]);
+
+    let mut recorder = AssertRecorder::new(context.row);
+
+    for (message, success) in checks {
+        recorder.assert(&message, success, ui, step_result).await?;
+    }
+
+    Ok(recorder.result)
+}
+
+async fn execute_command(invocation: Result<Invocation>, environment: HashMap<String, String>, stdin: Option<Vec<u8>>, options: &ExecOptions, context: StepContext, ui: &mut Ui, step_result: &mut TestStepResult) -> Result<TestResult> {
+    let started = SystemTime::now();
+    let mut recorder = AssertRecorder::new(context.row);
+
+    //A missing interpreter is the step's problem, not a timeout.
+    let started_command = invocation.and_then(|invocation| {
+        step_result.interpreter = Some(invocation.interpreter.clone());
+        RunningCommand::new(invocation, &environment, stdin, options)
+    });
+
+    let mut exec = match started_command {
+        Ok(exec) => exec,
+        Err(e) => {
+            recorder.assert(&format!("Command can be started\n  {e}"), false, ui, step_result).await?;
+            return Ok(recorder.result);
+        }
+    };
+    let mut tap = TapParser::new();
+    let mut timed_out = false;
+    let mut output = CommandOutput::default();
+    let row = context.row;
+
+    //Protocol messages on stdout aren't part of the output a step is checked against.
+    let is_message = |line: &String| options.channel == ProtocolChannel::Stdout && context.protocol == Protocol::Weave && line.starts_with("WEAVE-TEST:");
 
     'check_lines: loop {
-        let line: Result<Option<String>> = exec.next_line().await;
+        let line: Result<Option<OutputLine>> = exec.next_line().await;
 
         match line {
             Ok(val) => {
                 if let Some(line) = val {
+                    //Protocol messages only come from the side channel if the step has one.
+                    let line = match line {
+                        OutputLine::Channel(line) => line,
+                        OutputLine::Stdout(line) => {
+                            if !is_message(&line) {
+                                output.stdout.push(line.clone());
+                            }
+
+                            if options.channel != ProtocolChannel::Stdout {
+                                continue 'check_lines;
+                            }
+
+                            line
+                        }
+                        OutputLine::Stderr(line) => {
+                            if !is_message(&line) {
+                                output.stderr.push(line.clone());
+                            }
+
+                            if options.channel != ProtocolChannel::Stdout || context.protocol != Protocol::Weave {
+                                continue 'check_lines;
+                            }
+
+                            line
+                        }
+                    };
+
+                    if context.protocol == Protocol::Tap {
+                        let events = tap.parse_line(&line);
+                        report_tap(events, ui, step_result, &mut recorder).await?;
+                        continue 'check_lines;
+                    }
+
                     if let Some(txt) = line.strip_prefix("WEAVE-TEST:PRINT:") {
                         ui.print(txt).await?;
                     }
-                    if let Some(txt) = line.strip_prefix("WEAVE-TEST:FAIL:") {
-                        result = TestResult::Fail;
-
-                        ui.assert(txt, false).await?;
+                    if let Some(txt) = line.strip_prefix("WEAVE-TEST:ATTACH:") {
+                        let (path, label) = artifacts::parse_attach(txt);
+
+                        match artifacts::store(&path, &options.cwd, &context.artifact_dir) {
+                            Ok(stored) => {
+                                ui.print(&format!("Attached {}", label.clone().unwrap_or(stored.clone()))).await?;
+
+                                step_result.artifacts.push(Artifact {
+                                    path: stored,
+                                    label,
+                                    data_set_row: row,
+                                });
+                            }
+                            Err(e) => {
+                                ui.print(&format!("Unable to attach {path}: {e}")).await?;
+                            }
+                        }
+                    }
 
-                        asserts.push(AssertResult {
-                            message:txt.to_string(),
-                            success: false,
-                            data_set_row: row,
-                        });
+                    if let Some(txt) = line.strip_prefix("WEAVE-TEST:FAIL:") {
+                        recorder.assert(txt, false, ui, step_result).await?;
                     }
 
                     if let Some(txt) = line.strip_prefix("WEAVE-TEST:PASS:") {
-                        if result != TestResult::Fail {
-                            result = TestResult::Pass;
-                        }
-
+                        recorder.assert(txt, true, ui, step_result).await?;
+                    }
 
-                        ui.assert(txt, true).await?;
+                    if let Some(txt) = line.strip_prefix("WEAVE-TEST:GROUP:") {
+                        recorder.start_group(txt.trim(), ui).await?;
+                    }
 
-                        asserts.push(AssertResult {
-                            message: txt.to_string(),
-                            success: true,
-                            data_set_row: row,
-                        });
+                    if line.trim_end() == "WEAVE-TEST:ENDGROUP" || line.starts_with("WEAVE-TEST:ENDGROUP:") {
+                        recorder.end_group(ui, step_result).await?;
                     }
                 } else {
                     break 'check_lines
                 }
             }
             Err(_) => {
-                result = TestResult::Fail;
-                ui.assert("Test Timed Out!", false).await?;
-
-                asserts.push(AssertResult {
-                    message: "Test Timed Out!".to_string(),
-                    success: false,
-                    data_set_row: row,
-                });
-
+                timed_out = true;
+                recorder.assert("Test Timed Out!", false, ui, step_result).await?;
+                stop_command(&mut exec, row, ui, step_result).await?;
                 break 'check_lines;
             }
         };
     };
 
-    Ok(result.clone())
+    if !timed_out {
+        for process in exec.leftovers().await {
+            let message = format!("{process} was still running when the step finished");
+            ui.warning(&message).await?;
+            step_result.warnings.push(Warning { message, data_set_row: row });
+        }
+    }
+
+    if let (Some(limits), false) = (options.limits, timed_out) {
+        if let Ok(exit) = exec.exit().await {
+            for limit in limits.exceeded(&exit) {
+                recorder.assert(&format!("Stayed within the {limit}"), false, ui, step_result).await?;
+            }
+        }
+
+        for limit in limits.suspected(&output.stderr) {
+            let message = format!("stderr suggests the {limit} was hit");
+            ui.warning(&message).await?;
+            step_result.warnings.push(Warning { message, data_set_row: row });
+        }
+    }
+
+    if context.protocol == Protocol::Tap {
+        let events = tap.finish();
+        report_tap(events, ui, step_result, &mut recorder).await?;
+
+        if let Some(reason) = tap.skip_all() {
+            if recorder.result != TestResult::Fail {
+                ui.print(&format!("Skipped: {reason}")).await?;
+                recorder.result = TestResult::Skip;
+            }
+        }
+    }
+
+    if let (Some(pattern), false) = (&context.junit_results, timed_out) {
+        match junit::load_results(pattern, &options.cwd, started, row) {
+            Ok(cases) => report_junit(cases, ui, step_result, &mut recorder.result).await?,
+            Err(e) => recorder.assert(&e.to_string(), false, ui, step_result).await?,
+        }
+    }
+
+    if let (Some(expectations), false) = (&context.expect, timed_out) {
+        match exec.exit_code().await {
+            Ok(code) => {
+                output.exit_code = code;
+
+                for (message, success) in expect::evaluate(expectations, &output) {
+                    recorder.assert(&message, success, ui, step_result).await?;
+                }
+            }
+            Err(_) => {
+                recorder.assert("Test Timed Out!", false, ui, step_result).await?;
+                stop_command(&mut exec, row, ui, step_result).await?;
+            }
+        }
+    }
+
+    if let (Some(snapshot), false) = (&context.snapshot, timed_out) {
+        match snapshot.check(&output.stdout) {
+            Ok((message, success)) => recorder.assert(&message, success, ui, step_result).await?,
+            Err(e) => recorder.assert(&format!("snapshot {}: {e}", snapshot.name), false, ui, step_result).await?,
+        }
+    }
+
+    //Groups the script didn't close are closed for it.
+    recorder.finish(ui, step_result).await?;
+
+    Ok(recorder.result)
 
-}
\ No newline at end of file
+}
+
+/// Stops a command that timed out along with everything it started, warning about any process that had to be killed.
+async fn stop_command(exec: &mut RunningCommand, row: Option<usize>, ui: &mut Ui, result: &mut TestStepResult) -> Result<()> {
+    for message in exec.stop().await {
+        ui.warning(&message).await?;
+        result.warnings.push(Warning { message, data_set_row: row });
+    }
+
+    Ok(())
+}
+
+/// Collects the asserts a step reports, nesting them under any groups that are open.
+struct AssertRecorder {
+    row: Option<usize>,
+    result: TestResult,
+    groups: Vec<AssertResult>,
+}
+
+impl AssertRecorder {
+    fn new(row: Option<usize>) -> AssertRecorder {
+        AssertRecorder {
+            row,
+            result: TestResult::Inconclusive,
+            groups: vec![],
+        }
+    }
+
+    async fn assert(&mut self, message: &str, success: bool, ui: &mut Ui, step_result: &mut TestStepResult) -> Result<()> {
+        if !success {
+            self.result = TestResult::Fail;
+        } else if self.result != TestResult::Fail {
+            self.result = TestResult::Pass;
+        }
+
+        ui.assert(message, success).await?;
+
+        let assert = AssertResult::new(message, success, self.row);
+        self.push(assert, step_result);
+
+        Ok(())
+    }
+
+    async fn start_group(&mut self, name: &str, ui: &mut Ui) -> Result<()> {
+        ui.start_group(name).await?;
+        self.groups.push(AssertResult::new(name, true, self.row));
+
+        Ok(())
+    }
+
+    async fn end_group(&mut self, ui: &mut Ui, step_result: &mut TestStepResult) -> Result<()> {
+        let Some(mut group) = self.groups.pop() else {
+            return Ok(());
+        };
+
+        group.success = group.children.iter().all(|c| c.success);
+        ui.finish_group(&group.message, group.success).await?;
+        self.push(group, step_result);
+
+        Ok(())
+    }
+
+    async fn finish(&mut self, ui: &mut Ui, step_result: &mut TestStepResult) -> Result<()> {
+        while !self.groups.is_empty() {
+            self.end_group(ui, step_result).await?;
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, assert: AssertResult, step_result: &mut TestStepResult) {
+        match self.groups.last_mut() {
+            Some(group) => group.children.push(assert),
+            None => step_result.asserts.push(assert),
+        }
+    }
+}
+
+async fn report_junit(cases: Vec<TestStepResult>, ui: &mut Ui, step_result: &mut TestStepResult, result: &mut TestResult) -> Result<()> {
+    for case in cases {
+        let name = case.name.clone().unwrap_or_default();
+        let name = match case.duration {
+            Some(duration) => format!("{name} ({duration:.3}s)"),
+            None => name,
+        };
+
+        match case.result {
+            TestResult::Fail => {
+                *result = TestResult::Fail;
+                ui.assert(&name, false).await?;
+
+                for assert in &case.asserts {
+                    ui.print(&assert.message).await?;
+                }
+            }
+            TestResult::Skip => {
+                ui.print(&format!("Skipped {name}")).await?;
+            }
+            _ => {
+                if *result != TestResult::Fail {
+                    *result = TestResult::Pass;
+                }
+
+                ui.assert(&name, true).await?;
+            }
+        }
+
+        step_result.sub_steps.push(case);
+    }
+
+    Ok(())
+}
+
+async fn report_tap(events: Vec<TapEvent>, ui: &mut Ui, step_result: &mut TestStepResult, recorder: &mut AssertRecorder) -> Result<()> {
+    for event in events {
+        match event {
+            TapEvent::Assert { message, success } => {
+                recorder.assert(&message, success, ui, step_result).await?;
+            }
+            TapEvent::Print { message } => {
+                ui.print(&message).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use std::io::Write;
+use std::path::Path;
+use anyhow::{bail, Result};
+use crate::temp::TempPath;
+
+/// Shell commands are run with when a step or suite doesn't pick one.
+const DEFAULT_SHELL: &str = if cfg!(target_os = "windows") { "cmd" } else { "sh" };
+
+/// Name python 3 is installed under.
+const PYTHON: &str = if cfg!(target_os = "windows") { "python" } else { "python3" };
+
+/// Functions put in front of inline scripts so they can report results without a helper file.
+const SH_HELPERS: &str = include_str!("helpers/helpers.sh");
+const PYTHON_HELPERS: &str = include_str!("helpers/helpers.py");
+const POWERSHELL_HELPERS: &str = include_str!("helpers/helpers.ps1");
+const CMD_HELPERS: &str = include_str!("helpers/helpers.bat");
+
+/// A program and the arguments a step's command is started with.
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+    /// File the command or script was written to, removed again once the step is done.
+    pub script: Option<TempPath>,
+    /// How the command was run, kept in the results so a run can be reproduced.
+    pub interpreter: String,
+}
+
+/// How a named shell runs a command given on its command line and a script in a file.
+struct Shell {
+    program: &'static str,
+    command_flags: &'static [&'static str],
+    file_flags: &'static [&'static str],
+}
+
+fn named_shell(name: &str) -> Option<Shell> {
+    let (program, command_flags, file_flags): (&'static str, &'static [&'static str], &'static [&'static str]) = match name {
+        "sh" => ("sh", &["-c"], &[]),
+        "bash" => ("bash", &["-c"], &[]),
+        "zsh" => ("zsh", &["-c"], &[]),
+        "pwsh" => ("pwsh", &["-NoProfile", "-NonInteractive", "-Command"], &["-NoProfile", "-NonInteractive", "-File"]),
+        "powershell" => ("powershell", &["-NoProfile", "-NonInteractive", "-Command"], &["-NoProfile", "-NonInteractive", "-File"]),
+        "cmd" => ("cmd", &["/C"], &["/C"]),
+        "python" => (PYTHON, &["-c"], &[]),
+        _ => return None,
+    };
+
+    Some(Shell { program, command_flags, file_flags })
+}
+
+impl Invocation {
+    /// Runs a command with a named shell (sh, bash, zsh, pwsh, powershell, cmd or python) or a template such as
+    /// `python3 {file}`. A template without `{file}` gets the command as its last argument, like `node -e`.
+    pub fn shell(shell: Option<&str>, command: &str) -> Result<Invocation> {
+        let shell = shell_name(shell);
+
+        let Some(named) = named_shell(shell) else {
+            return Self::template(shell, command, false);
+        };
+
+        let mut args: Vec<String> = named.command_flags.iter().map(|f| f.to_string()).collect();
+        let interpreter = format!("{} {}", named.program, args.join(" "));
+        args.push(command.to_string());
+
+        Ok(Invocation {
+            program: named.program.to_string(),
+            args,
+            script: None,
+            interpreter,
+        })
+    }
+
+    /// Writes an inline script to a file, with the helper functions for its language in front, and runs it with
+    /// the shell. A template without `{file}` gets the file as its last argument.
+    pub fn script(shell: Option<&str>, body: &str) -> Result<Invocation> {
+        let shell = shell_name(shell);
+
+        let Some(named) = named_shell(shell) else {
+            return Self::template(shell, body, true);
+        };
+
+        let file = write_script(named.program, body, true)?;
+        let mut args: Vec<String> = named.file_flags.iter().map(|f| f.to_string()).collect();
+        let interpreter = format!("{} {}", named.program, [args.clone(), vec!["{file}".to_string()]].concat().join(" "));
+        args.push(file.path().to_string_lossy().to_string());
+
+        Ok(Invocation {
+            program: named.program.to_string(),
+            args,
+            script: Some(file),
+            interpreter,
+        })
+    }
+
+    /// Runs a program directly with no shell in between, so arguments don't need quoting.
+    pub fn direct(args: &[String]) -> Result<Invocation> {
+        let Some((program, args)) = args.split_first() else {
+            bail!("args needs at least the program to run");
+        };
+
+        Ok(Invocation {
+            program: program.clone(),
+            args: args.to_vec(),
+            script: None,
+            interpreter: program.clone(),
+        })
+    }
+
+    fn template(template: &str, text: &str, is_script: bool) -> Result<Invocation> {
+        let words: Vec<&str> = template.split_whitespace().collect();
+
+        let Some((program, rest)) = words.split_first() else {
+            bail!("Shell template {template} is empty");
+        };
+
+        let mut args: Vec<String> = rest.iter().map(|w| w.to_string()).collect();
+        let mut script = None;
+
+        if template.contains("{file}") || is_script {
+            let file = write_script(program, text, is_script)?;
+            let path = file.path().to_string_lossy().to_string();
+
+            if template.contains("{file}") {
+                args = args.iter().map(|a| a.replace("{file}", &path)).collect();
+            } else {
+                args.push(path);
+            }
+
+            script = Some(file);
+        } else {
+            args.push(text.to_string());
+        }
+
+        Ok(Invocation {
+            program: program.to_string(),
+            args,
+            script,
+            interpreter: template.to_string(),
+        })
+    }
+}
+
+fn shell_name(shell: Option<&str>) -> &str {
+    shell.map(|s| s.trim()).filter(|s| !s.is_empty()).unwrap_or(DEFAULT_SHELL)
+}
+
+/// Writes a command or script to a temp file with the extension the interpreter needs.
+fn write_script(program: &str, text: &str, with_helpers: bool) -> Result<TempPath> {
+    let extension = extension(program);
+    let (file, mut handle) = TempPath::create("script", extension)?;
+
+    let helpers = match extension {
+        ".sh" if with_helpers => SH_HELPERS,
+        ".py" if with_helpers => PYTHON_HELPERS,
+        ".ps1" if with_helpers => POWERSHELL_HELPERS,
+        ".bat" if with_helpers => CMD_HELPERS,
+        _ => "",
+    };
+
+    let script = match extension {
+        ".py" => after_future_imports(helpers, text),
+        //cmd can lose track of labels in a file that only uses \n, which would break calling the helpers.
+        ".bat" => format!("{helpers}{text}").replace("\r\n", "\n").replace('\n', "\r\n"),
+        _ => format!("{helpers}{text}"),
+    };
+
+    handle.write_all(script.as_bytes())?;
+
+    Ok(file)
+}
+
+/// Puts the helpers in front of a python script, but after any `from __future__` imports as they have to come first.
+fn after_future_imports(helpers: &str, text: &str) -> String {
+    let mut end = 0;
+    let mut offset = 0;
+    let mut open = false;
+
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+
+        //An import can be split over lines in brackets.
+        if line.starts_with("from __future__ import") {
+            open = line.contains('(') && !line.contains(')');
+            end = offset;
+        } else if open {
+            open = !line.contains(')');
+            end = offset;
+        }
+    }
+
+    match &text[..end] {
+        "" => format!("{helpers}{text}"),
+        imports if imports.ends_with('\n') => format!("{imports}{helpers}{}", &text[end..]),
+        imports => format!("{imports}\n{helpers}"),
+    }
+}
+
+/// File extension a script for the interpreter needs, some won't run files without the right one.
+fn extension(program: &str) -> &'static str {
+    let name = Path::new(program).file_stem().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+    match name.as_str() {
+        "pwsh" | "powershell" => ".ps1",
+        "cmd" => ".bat",
+        "node" | "deno" => ".js",
+        "ruby" => ".rb",
+        "perl" => ".pl",
+        n if n.starts_with("python") => ".py",
+        _ => ".sh",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn python_helpers_go_after_future_imports() {
+        let text = "\"\"\"Doc.\"\"\"\nfrom __future__ import annotations\nfrom __future__ import (\n    division,\n)\nprint(1)\n";
+        let script = after_future_imports("#helpers\n", text);
+
+        assert_eq!(script, "\"\"\"Doc.\"\"\"\nfrom __future__ import annotations\nfrom __future__ import (\n    division,\n)\n#helpers\nprint(1)\n");
+        assert_eq!(after_future_imports("#helpers\n", "print(1)\n"), "#helpers\nprint(1)\n");
+        assert_eq!(after_future_imports("#helpers\n", "from __future__ import annotations"), "from __future__ import annotations\n#helpers\n");
+    }
+
+    #[test]
+    fn cmd_scripts_get_helpers_and_crlf() {
+        let file = write_script("cmd", "call :assert_pass \"ok\"\r\necho done\n", true).unwrap();
+        let script = fs::read_to_string(file.path()).unwrap();
+
+        assert!(script.contains(":assert_pass\r\n"));
+        assert!(script.ends_with(":weave_test_script\r\ncall :assert_pass \"ok\"\r\necho done\r\n"));
+        assert!(!script.replace("\r\n", "").contains('\n'));
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use regex::Regex;
+use similar::TextDiff;
+use crate::artifacts::sanitize;
+
+/// Stored output a step is compared against. Snapshots live in `__snapshots__/<suite file stem>` next to the suite
+/// file, so suites in the same folder can use the same snapshot names.
+pub struct Snapshot {
+    pub name: String,
+    path: PathBuf,
+    redactions: Vec<String>,
+    update: bool,
+}
+
+impl Snapshot {
+    pub fn new(suite_path: &Path, name: &str, row: Option<usize>, redactions: Vec<String>, update: bool) -> Snapshot {
+        let stem = suite_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let folder = suite_path.parent().unwrap_or(Path::new(".")).join("__snapshots__").join(sanitize(&stem));
+
+        //Each data set row gets its own snapshot.
+        let file = match row {
+            Some(idx) => format!("{}.row-{idx}.snap", sanitize(name)),
+            None => format!("{}.snap", sanitize(name)),
+        };
+
+        Snapshot {
+            name: name.to_string(),
+            path: folder.join(file),
+            redactions,
+            update,
+        }
+    }
+
+    /// Compares the output to the stored snapshot, or stores it if snapshots are being updated.
+    pub fn check(&self, output: &[String]) -> Result<(String, bool)> {
+        let actual = self.redact(output)?;
+
+        if self.update {
+            if let Some(folder) = self.path.parent() {
+                fs::create_dir_all(folder)?;
+            }
+
+            fs::write(&self.path, &actual)?;
+            return Ok((format!("snapshot {} updated", self.name), true));
+        }
+
+        if !self.path.exists() {
+            return Ok((format!("snapshot {} matches\n  {} doesn't exist, run with --update-snapshots to record it", self.name, self.path.display()), false));
+        }
+
+        let stored = fs::read_to_string(&self.path)?.replace("\r\n", "\n");
+
+        if stored == actual {
+            return Ok((format!("snapshot {} matches", self.name), true));
+        }
+
+        let diff = TextDiff::from_lines(&stored, &actual)
+            .unified_diff()
+            .context_radius(3)
+            .header("stored", "actual")
+            .to_string();
+
+        Ok((format!("snapshot {} matches\n{}", self.name, diff.trim_end()), false))
+    }
+
+    /// Replaces anything matching the redaction patterns so volatile values don't break the comparison.
+    fn redact(&self, output: &[String]) -> Result<String> {
+        let mut text = output.join("\n");
+
+        for pattern in &self.redactions {
+            let re = Regex::new(pattern)?;
+            text = re.replace_all(&text, "[REDACTED]").to_string();
+        }
+
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_are_kept_per_suite() {
+        let first = Snapshot::new(Path::new("tests/web_test.yaml"), "report", None, vec![], false);
+        let second = Snapshot::new(Path::new("tests/db_test.yaml"), "report", None, vec![], false);
+
+        assert_eq!(first.path, Path::new("tests/__snapshots__/web_test/report.snap"));
+        assert_eq!(second.path, Path::new("tests/__snapshots__/db_test/report.snap"));
+    }
+
+    #[test]
+    fn rows_get_their_own_snapshot() {
+        let snapshot = Snapshot::new(Path::new("web_test.yaml"), "host check", Some(1), vec![], false);
+
+        assert_eq!(snapshot.path, Path::new("__snapshots__/web_test/host_check.row-1.snap"));
+    }
+}
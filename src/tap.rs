@@ -0,0 +1,259 @@
+use regex::Regex;
+
+/// Something a TAP producer told us that needs to be reported.
+pub enum TapEvent {
+    Assert{message: String, success: bool},
+    Print{message: String},
+}
+
+struct PendingTest {
+    message: String,
+    success: bool,
+    diagnostics: Vec<String>,
+}
+
+/// Line by line parser for the Test Anything Protocol (https://testanything.org).
+pub struct TapParser {
+    plan_pattern: Regex,
+    test_pattern: Regex,
+    plan: Option<usize>,
+    skip_all: Option<String>,
+    count: usize,
+    in_yaml: bool,
+    pending: Option<PendingTest>,
+}
+
+impl TapParser {
+    pub fn new() -> TapParser {
+        TapParser {
+            plan_pattern: Regex::new(r"^1\.\.(\d+)\s*(?:#\s*(.*))?$").unwrap(),
+            test_pattern: Regex::new(r"^(not )?ok\b\s*(\d+)?\s*(?:-\s*)?(.*?)\s*(?:#\s*(?i:(skip|todo))\S*\s*(.*))?$").unwrap(),
+            plan: None,
+            skip_all: None,
+            count: 0,
+            in_yaml: false,
+            pending: None,
+        }
+    }
+
+    pub fn parse_line(&mut self, line: &str) -> Vec<TapEvent> {
+        let mut events = vec![];
+
+        //YAML diagnostics belong to the test line just before them. They're indented, so a line that isn't ends a
+        //block that was never closed.
+        if self.in_yaml && !line.is_empty() && !line.starts_with(char::is_whitespace) {
+            self.in_yaml = false;
+        }
+
+        if self.in_yaml {
+            if line.trim() == "..." {
+                self.in_yaml = false;
+            } else if let Some(pending) = &mut self.pending {
+                pending.diagnostics.push(line.to_string());
+            }
+
+            return events;
+        }
+
+        if line.trim() == "---" && line.starts_with(char::is_whitespace) && self.pending.is_some() {
+            self.in_yaml = true;
+            return events;
+        }
+
+        self.flush(&mut events);
+
+        if let Some(caps) = self.plan_pattern.captures(line) {
+            let planned: usize = caps[1].parse().unwrap_or(0);
+            self.plan = Some(planned);
+
+            if planned == 0 {
+                self.skip_all = Some(caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default());
+            }
+
+            return events;
+        }
+
+        if let Some(caps) = self.test_pattern.captures(line) {
+            self.count += 1;
+
+            let ok = caps.get(1).is_none();
+            let number = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or(self.count.to_string());
+            let description = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+            let mut message = format!("{number} {description}").trim_end().to_string();
+
+            //Skipped tests and tests still to do never fail the step.
+            let success = match caps.get(4).map(|m| m.as_str().to_lowercase()) {
+                Some(directive) => {
+                    let reason = caps.get(5).map(|m| m.as_str()).unwrap_or("");
+                    message = format!("{message} ({directive} {reason})").replace(" )", ")");
+                    true
+                }
+                None => ok,
+            };
+
+            self.pending = Some(PendingTest { message, success, diagnostics: vec![] });
+            return events;
+        }
+
+        if let Some(reason) = line.strip_prefix("Bail out!") {
+            events.push(TapEvent::Assert { message: format!("Bail out! {}", reason.trim()), success: false });
+            return events;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            events.push(TapEvent::Print { message: comment.trim().to_string() });
+        }
+
+        events
+    }
+
+    /// Called once the producer has exited. Checks the plan against the tests that were seen.
+    pub fn finish(&mut self) -> Vec<TapEvent> {
+        let mut events = vec![];
+        self.flush(&mut events);
+
+        match self.plan {
+            None => {
+                events.push(TapEvent::Assert { message: "No TAP plan was found!".to_string(), success: false });
+            }
+            Some(planned) if planned != self.count => {
+                events.push(TapEvent::Assert { message: format!("Planned {planned} tests but {} ran!", self.count), success: false });
+            }
+            _ => {}
+        }
+
+        events
+    }
+
+    /// Set if the producer skipped everything with a `1..0` plan.
+    pub fn skip_all(&self) -> Option<&String> {
+        self.skip_all.as_ref()
+    }
+
+    fn flush(&mut self, events: &mut Vec<TapEvent>) {
+        if let Some(pending) = self.pending.take() {
+            let mut message = pending.message;
+
+            for line in pending.diagnostics {
+                message = format!("{message}\n{line}");
+            }
+
+            events.push(TapEvent::Assert { message, success: pending.success });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `text` through a parser, returning the asserts as `(message, success)` and the prints.
+    fn parse(text: &str) -> (Vec<(String, bool)>, Vec<String>, TapParser) {
+        let mut parser = TapParser::new();
+        let mut events = vec![];
+
+        for line in text.lines() {
+            events.extend(parser.parse_line(line));
+        }
+
+        events.extend(parser.finish());
+
+        let mut asserts = vec![];
+        let mut prints = vec![];
+
+        for event in events {
+            match event {
+                TapEvent::Assert { message, success } => asserts.push((message, success)),
+                TapEvent::Print { message } => prints.push(message),
+            }
+        }
+
+        (asserts, prints, parser)
+    }
+
+    #[test]
+    fn plan_at_the_end() {
+        let (asserts, _, _) = parse("ok 1 - first\nok 2 - second\n1..2");
+
+        assert_eq!(asserts, vec![("1 first".to_string(), true), ("2 second".to_string(), true)]);
+    }
+
+    #[test]
+    fn plan_that_doesnt_match() {
+        let (asserts, _, _) = parse("1..3\nok 1\nok 2");
+
+        assert_eq!(asserts.last(), Some(&("Planned 3 tests but 2 ran!".to_string(), false)));
+    }
+
+    #[test]
+    fn missing_plan() {
+        let (asserts, _, _) = parse("ok 1 - only");
+
+        assert_eq!(asserts.last(), Some(&("No TAP plan was found!".to_string(), false)));
+    }
+
+    #[test]
+    fn todo_failure_doesnt_fail() {
+        let (asserts, _, _) = parse("1..2\nnot ok 1 - not done # TODO later\nnot ok 2 - broken");
+
+        assert_eq!(asserts, vec![("1 not done (todo later)".to_string(), true), ("2 broken".to_string(), false)]);
+    }
+
+    #[test]
+    fn skip_without_reason() {
+        let (asserts, _, _) = parse("1..1\nok 1 - windows only # skip");
+
+        assert_eq!(asserts, vec![("1 windows only (skip)".to_string(), true)]);
+    }
+
+    #[test]
+    fn hash_in_description() {
+        let (asserts, _, _) = parse("1..1\nok 1 - fixes #12");
+
+        assert_eq!(asserts, vec![("1 fixes #12".to_string(), true)]);
+    }
+
+    #[test]
+    fn numbers_filled_in_when_missing() {
+        let (asserts, _, _) = parse("1..2\nok\nnot ok");
+
+        assert_eq!(asserts, vec![("1".to_string(), true), ("2".to_string(), false)]);
+    }
+
+    #[test]
+    fn yaml_diagnostics_join_their_test() {
+        let (asserts, _, _) = parse("1..1\nnot ok 1 - sum\n  ---\n  got: 3\n  ...\n# done");
+
+        assert_eq!(asserts, vec![("1 sum\n  got: 3".to_string(), false)]);
+    }
+
+    #[test]
+    fn unterminated_yaml_ends_at_the_next_test() {
+        let (asserts, _, _) = parse("1..2\nnot ok 1 - sum\n  ---\n  got: 3\nok 2");
+
+        assert_eq!(asserts, vec![("1 sum\n  got: 3".to_string(), false), ("2".to_string(), true)]);
+    }
+
+    #[test]
+    fn bail_out_fails() {
+        let (asserts, _, _) = parse("1..2\nok 1\nBail out! database is down");
+
+        assert!(asserts.contains(&("Bail out! database is down".to_string(), false)));
+    }
+
+    #[test]
+    fn comments_are_printed_and_noise_ignored() {
+        let (asserts, prints, _) = parse("TAP version 13\n# starting\nokay then\n1..1\nok 1");
+
+        assert_eq!(prints, vec!["starting".to_string()]);
+        assert_eq!(asserts, vec![("1".to_string(), true)]);
+    }
+
+    #[test]
+    fn skip_all() {
+        let (asserts, _, parser) = parse("1..0 # SKIP no database");
+
+        assert!(asserts.is_empty());
+        assert_eq!(parser.skip_all(), Some(&"SKIP no database".to_string()));
+    }
+}
@@ -0,0 +1,104 @@
+use std::env::temp_dir;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Result};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A path in the system temp folder that is removed again when dropped.
+pub struct TempPath {
+    path: PathBuf,
+}
+
+impl TempPath {
+    pub fn new(prefix: &str, extension: &str) -> TempPath {
+        let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let name = format!("weave-test-{prefix}-{}-{count}-{nanos}{extension}", process::id());
+
+        TempPath {
+            path: temp_dir().join(name),
+        }
+    }
+
+    /// Creates a new empty file that only we can read and write. Anything already at the path, like a symlink someone
+    /// else planted, is never opened, another name is tried instead.
+    pub fn create(prefix: &str, extension: &str) -> Result<(TempPath, File)> {
+        for _ in 0..100 {
+            let path = Self::new(prefix, extension);
+
+            match open_new(&path.path) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    //It isn't ours, so it mustn't be removed.
+                    std::mem::forget(path);
+                }
+                Err(e) => bail!("Unable to create temp file {}: {e}", path.path.display()),
+            }
+        }
+
+        bail!("Unable to create a temp file in {}", temp_dir().display())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn open_new(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn created_files_are_new() {
+        let (first, _) = TempPath::create("test", ".txt").unwrap();
+        let (second, _) = TempPath::create("test", ".txt").unwrap();
+
+        assert_ne!(first.path(), second.path());
+        assert!(open_new(first.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn created_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (file, _) = TempPath::create("test", ".txt").unwrap();
+
+        assert_eq!(fs::metadata(file.path()).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_followed() {
+        let (target, _) = TempPath::create("test", ".target").unwrap();
+        let link = TempPath::new("test", ".link");
+        std::os::unix::fs::symlink(target.path(), link.path()).unwrap();
+
+        assert_eq!(open_new(link.path()).unwrap_err().kind(), ErrorKind::AlreadyExists);
+    }
+}
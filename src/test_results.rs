@@ -23,6 +23,18 @@ pub struct TestStepResult {
     pub name: Option<String>,
     pub result: TestResult,
     pub asserts: Vec<AssertResult>,
+    pub artifacts: Vec<Artifact>,
+    pub sub_steps: Vec<TestStepResult>,
+    pub duration: Option<f64>,
+    /// Seconds each data set row took to run.
+    pub row_durations: Vec<f64>,
+    /// Result of each data set row, rows left out when the suite or session ran out of time are `NotRun`.
+    pub row_results: Vec<TestResult>,
+    pub warnings: Vec<Warning>,
+    /// Times each run was tried when waiting until it passes, one per data set row.
+    pub attempts: Vec<u32>,
+    /// How the step's command was run, such as `sh -c` or `python3 {file}`.
+    pub interpreter: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,6 +42,50 @@ pub struct AssertResult {
     pub message: String,
     pub success: bool,
     pub data_set_row: Option<usize>,
+    pub children: Vec<AssertResult>,
+}
+
+/// Something worth knowing about a step that doesn't fail it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Warning {
+    pub message: String,
+    pub data_set_row: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Artifact {
+    pub path: String,
+    pub label: Option<String>,
+    pub data_set_row: Option<usize>,
+}
+
+impl TestStepResult {
+    pub fn new(name: Option<String>, result: TestResult) -> TestStepResult {
+        TestStepResult {
+            name,
+            result,
+            asserts: vec![],
+            artifacts: vec![],
+            sub_steps: vec![],
+            duration: None,
+            row_durations: vec![],
+            row_results: vec![],
+            warnings: vec![],
+            attempts: vec![],
+            interpreter: None,
+        }
+    }
+}
+
+impl AssertResult {
+    pub fn new(message: &str, success: bool, data_set_row: Option<usize>) -> AssertResult {
+        AssertResult {
+            message: message.to_string(),
+            success,
+            data_set_row,
+            children: vec![],
+        }
+    }
 }
 
 impl fmt::Display for TestResult {
@@ -1,8 +1,18 @@
 use std::collections::HashMap;
 use std::fs;
-use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use anyhow::{bail, Result};
 use glob::glob;
+use crate::checks::file::FileCheck;
+use crate::checks::http::HttpCheck;
+use crate::checks::package::PackageCheck;
+use crate::checks::port::PortCheck;
+use crate::checks::process::ProcessCheck;
+use crate::checks::service::ServiceCheck;
+use crate::checks::user::{GroupCheck, UserCheck};
+use crate::limits::Limits;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TestSuite {
@@ -10,8 +20,21 @@ pub struct TestSuite {
     pub author: Option<String>,
     pub description: Option<String>,
     pub env: Option<HashMap<String, String>>,
+    /// Folder steps run in, relative to the suite file. Defaults to the folder the suite is in.
+    pub cwd: Option<String>,
+    /// Shell steps run their commands with unless they set their own.
+    pub shell: Option<String>,
+    pub limits: Option<Limits>,
+    pub env_mode: Option<EnvMode>,
+    pub env_passthrough: Option<Vec<String>>,
+    pub run_as: Option<String>,
+    /// Time all the suite's steps have between them. Steps still to run once it's used up are marked as not run.
+    pub timeout: Option<HumanDuration>,
     pub steps: Vec<TestStep>,
-    pub data_sets: Option<HashMap<String, Vec<HashMap<String, String>>>>
+    pub data_sets: Option<HashMap<String, Vec<HashMap<String, String>>>>,
+    pub channel: Option<ProtocolChannel>,
+    #[serde(skip)]
+    pub path: PathBuf,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,10 +42,213 @@ pub struct TestStep {
     pub name: Option<String>,
     pub description: Option<String>,
     pub skip: Option<bool>,
-    pub command: String,
+    pub command: Option<String>,
+    /// Program and arguments to run directly, without a shell.
+    pub args: Option<Vec<String>>,
+    /// Script to run, written to a temp file with the helper functions like `assert_pass` added.
+    pub script: Option<String>,
+    /// Text written to the command's stdin, with `${NAME}` replaced by environment and data set values.
+    pub stdin: Option<String>,
+    /// File written to the command's stdin, relative to the step's folder. Text files are interpolated like `stdin`.
+    pub stdin_file: Option<String>,
+    /// Shell the command is run with, such as `bash` or `pwsh`, or a template like `python3 {file}`.
+    pub shell: Option<String>,
     pub env: Option<HashMap<String, String>>,
+    /// How much of weave-test's own environment the command sees. Overrides the suite's `env_mode`.
+    pub env_mode: Option<EnvMode>,
+    /// Variables passed through from weave-test's environment with the `allowlist` mode. A trailing `*` matches
+    /// any variable starting with the rest, such as `LC_*`.
+    pub env_passthrough: Option<Vec<String>>,
+    /// User the command runs as, `user` or `user:group`, with `HOME` and `USER` set to match. Needs weave-test to
+    /// run as root. Built-in checks still run as weave-test.
+    pub run_as: Option<String>,
+    /// Folder the step runs in, relative to the suite file. Overrides the suite's `cwd`.
+    pub cwd: Option<String>,
     pub data_set: Option<String>,
-    pub timeout: Option<u64>,
+    /// Time each run of the command has before it's stopped. Every data set row and `wait_until` attempt gets
+    /// the whole of it. Defaults to 5 minutes.
+    pub timeout: Option<HumanDuration>,
+    /// Time the step should finish within. Unlike the timeout the command isn't stopped.
+    pub max_duration: Option<HumanDuration>,
+    pub max_duration_action: Option<LimitAction>,
+    pub wait_until: Option<WaitUntil>,
+    pub limits: Option<Limits>,
+    pub channel: Option<ProtocolChannel>,
+    pub protocol: Option<Protocol>,
+    pub junit_results: Option<String>,
+    pub expect: Option<Expectations>,
+    pub snapshot: Option<String>,
+    pub snapshot_redact: Option<Vec<String>>,
+    pub file: Option<FileCheck>,
+    pub port: Option<PortCheck>,
+    pub http: Option<HttpCheck>,
+    pub process: Option<ProcessCheck>,
+    pub service: Option<ServiceCheck>,
+    pub user: Option<UserCheck>,
+    pub group: Option<GroupCheck>,
+    pub package: Option<PackageCheck>,
+}
+
+impl TestStep {
+    /// The keys that say what a step does. A step can only have one of them.
+    pub fn actions(&self) -> Vec<&'static str> {
+        [
+            ("command", self.command.is_some()),
+            ("args", self.args.is_some()),
+            ("script", self.script.is_some()),
+            ("file", self.file.is_some()),
+            ("port", self.port.is_some()),
+            ("http", self.http.is_some()),
+            ("process", self.process.is_some()),
+            ("service", self.service.is_some()),
+            ("user", self.user.is_some()),
+            ("group", self.group.is_some()),
+            ("package", self.package.is_some()),
+        ].into_iter().filter(|(_, set)| *set).map(|(key, _)| key).collect()
+    }
+}
+
+/// Checks made against a command's output and exit code once it has finished.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Expectations {
+    pub exit_code: Option<i32>,
+    pub stdout_contains: Option<OneOrMany>,
+    pub stdout_not_contains: Option<OneOrMany>,
+    pub stdout_matches: Option<OneOrMany>,
+    /// Not available on Windows, where stderr isn't captured.
+    pub stderr_empty: Option<bool>,
+    pub json: Option<Vec<JsonExpectation>>,
+}
+
+/// Checks made against the value at a path in JSON output. Only the checks that are set are made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JsonExpectation {
+    pub path: String,
+    pub equals: Option<serde_json::Value>,
+    pub matches: Option<String>,
+    pub exists: Option<bool>,
+    #[serde(rename = "type")]
+    pub value_type: Option<String>,
+    pub length: Option<usize>,
+}
+
+/// Lets a setting be written as a single value or a list of them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn items(&self) -> Vec<&String> {
+        match self {
+            OneOrMany::One(item) => vec![item],
+            OneOrMany::Many(items) => items.iter().collect(),
+        }
+    }
+}
+
+/// A length of time, written as a number of seconds or a string like `90s`, `5m` or `1h30m`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn parse(text: &str) -> Result<HumanDuration> {
+        match text.trim().parse::<f64>() {
+            Ok(seconds) => Self::seconds(seconds),
+            Err(_) => match humantime::parse_duration(text.trim()) {
+                Ok(duration) => Ok(HumanDuration(duration)),
+                Err(e) => bail!("Invalid duration {text}: {e}"),
+            },
+        }
+    }
+
+    fn seconds(seconds: f64) -> Result<HumanDuration> {
+        match Duration::try_from_secs_f64(seconds) {
+            Ok(duration) => Ok(HumanDuration(duration)),
+            Err(_) => bail!("Invalid duration {seconds}, it has to be a positive number of seconds"),
+        }
+    }
+}
+
+impl std::fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", humantime::format_duration(self.0))
+    }
+}
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Written {
+            Seconds(f64),
+            Text(String),
+        }
+
+        match Written::deserialize(deserializer)? {
+            Written::Seconds(seconds) => HumanDuration::seconds(seconds).map_err(serde::de::Error::custom),
+            Written::Text(text) => HumanDuration::parse(&text).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Runs a step again and again until it passes, for things that take a while to settle.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WaitUntil {
+    /// Time the step has to pass within.
+    pub within: HumanDuration,
+    /// Time to wait between attempts, defaults to 1s.
+    pub interval: Option<HumanDuration>,
+}
+
+/// How much of weave-test's own environment a step's command starts with, before its `env` is added.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvMode {
+    /// All of it.
+    #[default]
+    Inherit,
+    /// None of it, not even `PATH`.
+    Clean,
+    /// Only the variables in `env_passthrough`.
+    Allowlist,
+}
+
+/// What happens when a step goes over a soft limit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitAction {
+    Fail,
+    Warn,
+}
+
+/// The format a step reports its results in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// `WEAVE-TEST:` prefixed messages.
+    Weave,
+    /// Test Anything Protocol output like bats or prove produce.
+    Tap,
+}
+
+/// Where a step writes its `WEAVE-TEST:` messages to. Everything other than stdout is passed to the
+/// step as a path in the `WEAVE_TEST_CHANNEL` environment variable.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolChannel {
+    Stdout,
+    File,
+    Fifo,
+    Pipe,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -45,7 +271,9 @@ pub fn load_from_folder(path: &str) -> Result<Vec<TestSuite>> {
         match entry{
             Ok(path) => {
                 let text = fs::read_to_string(&path)?;
-                let test = serde_yaml::from_str(text.as_str())?;
+                let mut test: TestSuite = serde_yaml::from_str(text.as_str())?;
+                validate(&test, &path)?;
+                test.path = fs::canonicalize(&path).unwrap_or(path);
                 result.push(test);
             },
             Err(e) => bail!(e),
@@ -53,4 +281,23 @@ pub fn load_from_folder(path: &str) -> Result<Vec<TestSuite>> {
     }
 
     Ok(result)
-}
\ No newline at end of file
+}
+
+/// Catches steps that are set up in a way that can't be run, before anything is.
+fn validate(suite: &TestSuite, path: &Path) -> Result<()> {
+    for (index, step) in suite.steps.iter().enumerate() {
+        let actions = step.actions();
+        let name = step.name.clone().unwrap_or(format!("{}", index + 1));
+
+        if actions.len() > 1 {
+            bail!("Step {name} in {} has {}, a step can only do one of these", path.display(), actions.join(" and "));
+        }
+
+        //Commands' stderr isn't captured on Windows, so it would always look empty.
+        if cfg!(windows) && step.expect.as_ref().is_some_and(|e| e.stderr_empty.is_some()) {
+            bail!("Step {name} in {} expects stderr_empty, which can't be checked on Windows", path.display());
+        }
+    }
+
+    Ok(())
+}
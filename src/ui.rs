@@ -21,13 +21,16 @@ pub enum UIFormat {
 }
 
 pub enum UIMessage {
-    Text{message: String},
+    Text{message: String, depth: usize},
     StartSuite{name: String},
     FinishSuite{name: String, state: TestResult, lines: u16},
     StartStep{name: String},
     FinishStep{name: String, state: TestResult, lines: u16},
     Finish,
-    Assert{message: String, success: bool},
+    Assert{message: String, success: bool, depth: usize},
+    Warning{message: String, depth: usize},
+    StartGroup{name: String, depth: usize},
+    FinishGroup{name: String, success: bool, lines: u16, depth: usize},
     ReportSetInstance {index: usize},
     StartSet {name: String},
     FinishSet,
@@ -38,7 +41,18 @@ pub struct Ui {
     _handler: Option<JoinHandle<Result<()>>>,
     lines_to_suite: u16,
     lines_to_step: u16,
+    lines_to_groups: Vec<u16>,
     format: UIFormat,
+    recording: Option<Vec<Recorded>>,
+}
+
+/// Output held back while the UI is recording, so it can be shown later.
+pub enum Recorded {
+    Text(String),
+    Assert(String, bool),
+    Warning(String),
+    StartGroup(String),
+    FinishGroup(String, bool),
 }
 
 impl FromStr for UIFormat {
@@ -89,7 +103,9 @@ impl Ui {
            _handler: handler,
            lines_to_suite: 0,
            lines_to_step: 0,
+           lines_to_groups: vec![],
            format,
+           recording: None,
        }
    }
 
@@ -101,10 +117,11 @@ impl Ui {
 
            while let Some(msg) = rx.recv().await {
                match msg {
-                   UIMessage::Text { message } => {
+                   UIMessage::Text { message, depth } => {
                        let message = message + "\n";
                        stdout
-                           .queue(style::PrintStyledContent("\t\tℹ ".blue()))?
+                           .queue(Print(indent(depth)))?
+                           .queue(style::PrintStyledContent("ℹ ".blue()))?
                            .queue(style::Print(message))?;
                    }
                    UIMessage::Finish => {
@@ -190,17 +207,48 @@ impl Ui {
                            .queue(Print(name))?
                            .queue(MoveDown(lines))?;
                    }
-                   UIMessage::Assert { message, success } => {
+                   UIMessage::Assert { message, success, depth } => {
                        let message = message + "\n";
 
+                       stdout.queue(Print(indent(depth)))?;
+
                        if success{
-                           stdout.queue(style::PrintStyledContent("\t\t✔ ".green()))?;
+                           stdout.queue(style::PrintStyledContent("✔ ".green()))?;
                        }else{
-                           stdout.queue(style::Print("\t\t✘ ".red()))?;
+                           stdout.queue(style::Print("✘ ".red()))?;
                        }
 
                        stdout.queue(style::Print(message))?;
                    },
+                   UIMessage::Warning { message, depth } => {
+                       stdout
+                           .queue(Print(indent(depth)))?
+                           .queue(PrintStyledContent("⚠ ".dark_yellow()))?
+                           .queue(Print(message + "\n"))?;
+                   }
+                   UIMessage::StartGroup { name, depth } => {
+                       stdout
+                           .queue(Print(indent(depth)))?
+                           .queue(PrintStyledContent("▸ ".yellow()))?
+                           .queue(Print(name + "\n"))?;
+                   }
+                   UIMessage::FinishGroup { name, success, lines, depth } => {
+                       stdout
+                           .queue(cursor::MoveUp(lines))?
+                           .queue(terminal::Clear(ClearType::CurrentLine))?
+                           .queue(Print(indent(depth)))?;
+
+                       if success{
+                           stdout.queue(PrintStyledContent("▾ ".green()))?;
+                       }else{
+                           stdout.queue(PrintStyledContent("▾ ".red()))?;
+                       }
+
+                       //Printing the heading already moved us down one line.
+                       stdout
+                           .queue(Print(name + "\n"))?
+                           .queue(MoveDown(lines.saturating_sub(1)))?;
+                   }
                    UIMessage::ReportSetInstance { index} => {
                        stdout.queue(PrintStyledContent(format!("\t⬛ - Row: {index}\n").blue()))?;
                    }
@@ -310,36 +358,70 @@ impl Ui {
         Ok(())
     }
 
+    /// Holds back output from inside a step instead of showing it, used while waiting for a step to pass.
+    pub fn record(&mut self) {
+        self.recording = Some(vec![]);
+    }
+
+    /// Stops recording and hands back what was held back since `record`.
+    pub fn take_recording(&mut self) -> Vec<Recorded> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Shows output that was recorded earlier as if it had just happened.
+    pub async fn play(&mut self, recorded: Vec<Recorded>) -> Result<()> {
+        for output in recorded {
+            match output {
+                Recorded::Text(text) => self.print(&text).await?,
+                Recorded::Assert(text, success) => self.assert(&text, success).await?,
+                Recorded::Warning(text) => self.warning(&text).await?,
+                Recorded::StartGroup(name) => self.start_group(&name).await?,
+                Recorded::FinishGroup(name, success) => self.finish_group(&name, success).await?,
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn print(&mut self, text: &str) -> Result<()>{
+        if let Some(recording) = &mut self.recording {
+            recording.push(Recorded::Text(text.to_string()));
+            return Ok(());
+        }
+
         if self.format == UIFormat::Colour {
 
             let sender = self.sender.as_ref().unwrap();
-            sender.send(UIMessage::Text { message: text.to_string() }).await?;
-            self.lines_to_suite += 1;
-            self.lines_to_step += 1;
+            sender.send(UIMessage::Text { message: text.to_string(), depth: self.lines_to_groups.len() }).await?;
+            self.add_lines(line_count(text));
         }
 
         if self.format == UIFormat::Plain {
-            println!("{text}");
+            println!("{}{text}", "  ".repeat(self.lines_to_groups.len()));
         }
 
         Ok(())
     }
 
     pub async fn assert(&mut self, text: &str, success: bool) -> Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.push(Recorded::Assert(text.to_string(), success));
+            return Ok(());
+        }
+
         if self.format == UIFormat::Colour {
             let sender = self.sender.as_ref().unwrap();
-            sender.send(UIMessage::Assert{ message: text.to_string(), success }).await?;
-            self.lines_to_suite += 1;
-            self.lines_to_step += 1;
+            sender.send(UIMessage::Assert{ message: text.to_string(), success, depth: self.lines_to_groups.len() }).await?;
+            self.add_lines(line_count(text));
         }
 
         if self.format == UIFormat::Plain {
+            let indent = "  ".repeat(self.lines_to_groups.len());
 
             if success{
-                println!("Assert Ok: {text}");
+                println!("{indent}Assert Ok: {text}");
             } else{
-                println!("Assert Failed: {text}");
+                println!("{indent}Assert Failed: {text}");
             }
         }
 
@@ -347,12 +429,83 @@ impl Ui {
         Ok(())
     }
 
+    pub async fn warning(&mut self, text: &str) -> Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.push(Recorded::Warning(text.to_string()));
+            return Ok(());
+        }
+
+        if self.format == UIFormat::Colour {
+            let sender = self.sender.as_ref().unwrap();
+            sender.send(UIMessage::Warning{ message: text.to_string(), depth: self.lines_to_groups.len() }).await?;
+            self.add_lines(line_count(text));
+        }
+
+        if self.format == UIFormat::Plain {
+            println!("{}Warning: {text}", "  ".repeat(self.lines_to_groups.len()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn start_group(&mut self, name: &str) -> Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.push(Recorded::StartGroup(name.to_string()));
+            return Ok(());
+        }
+
+        if self.format == UIFormat::Colour {
+            let sender = self.sender.as_ref().unwrap();
+            sender.send(UIMessage::StartGroup { name: name.to_string(), depth: self.lines_to_groups.len() }).await?;
+            self.add_lines(1);
+        }
+
+        if self.format == UIFormat::Plain {
+            println!("{}Starting Group {name}", "  ".repeat(self.lines_to_groups.len()));
+        }
+
+        //Every group counts lines back to its own heading.
+        self.lines_to_groups.push(1);
+
+        Ok(())
+    }
+
+    pub async fn finish_group(&mut self, name: &str, success: bool) -> Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.push(Recorded::FinishGroup(name.to_string(), success));
+            return Ok(());
+        }
+
+        let lines = self.lines_to_groups.pop().unwrap_or(1);
+        let depth = self.lines_to_groups.len();
+
+        if self.format == UIFormat::Colour {
+            let sender = self.sender.as_ref().unwrap();
+            sender.send(UIMessage::FinishGroup { name: name.to_string(), success, lines, depth }).await?;
+        }
+
+        if self.format == UIFormat::Plain {
+            let result = if success { TestResult::Pass } else { TestResult::Fail };
+            println!("{}Finished Group {name}: Result: {result}", "  ".repeat(depth));
+        }
+
+        Ok(())
+    }
+
+    fn add_lines(&mut self, count: u16) {
+        self.lines_to_suite += count;
+        self.lines_to_step += count;
+
+        for lines in self.lines_to_groups.iter_mut() {
+            *lines += count;
+        }
+    }
+
     pub async fn start_set(&mut self, name: &str) -> Result<()>{
         if self.format == UIFormat::Colour {
             let sender = self.sender.as_ref().unwrap();
             sender.send(UIMessage::StartSet {name: name.to_string()}).await?;
-            self.lines_to_suite += 1;
-            self.lines_to_step += 1;
+            self.add_lines(1);
         }
 
         if self.format == UIFormat::Plain {
@@ -366,8 +519,7 @@ impl Ui {
         if self.format == UIFormat::Colour {
             let sender = self.sender.as_ref().unwrap();
             sender.send(UIMessage::FinishSet).await?;
-            self.lines_to_suite += 1;
-            self.lines_to_step += 1;
+            self.add_lines(1);
         }
 
         if self.format == UIFormat::Plain {
@@ -381,8 +533,7 @@ impl Ui {
         if self.format == UIFormat::Colour {
             let sender = self.sender.as_ref().unwrap();
             sender.send(UIMessage::ReportSetInstance{index}).await?;
-            self.lines_to_suite += 1;
-            self.lines_to_step += 1;
+            self.add_lines(1);
         }
 
         if self.format == UIFormat::Plain {
@@ -393,3 +544,11 @@ impl Ui {
     }
 }
 
+fn indent(depth: usize) -> String {
+    format!("\t\t{}", "  ".repeat(depth))
+}
+
+/// Messages can span several lines which all need counting to redraw headings above them.
+fn line_count(text: &str) -> u16 {
+    text.lines().count().max(1) as u16
+}